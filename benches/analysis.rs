@@ -0,0 +1,149 @@
+// Throughput of the analysis core in frames per second.
+//
+//     cargo bench -- [SECONDS | FILE.wav]
+//
+// Runs over a synthetic melody of SECONDS seconds (default 600) or the
+// first channel of a WAV file.
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::env;
+use std::f32::consts::PI;
+use std::hint::black_box;
+use std::time::Instant;
+use wav_note_detector::batch;
+use wav_note_detector::decode;
+use wav_note_detector::fft::RealFft;
+use wav_note_detector::{Analyzer, AnalyzerConfig, ChannelMode, ChromaConfig, DetectorKind};
+
+const SAMPLE_RATE: f32 = 44100.0;
+
+// A note a second with a few harmonics, C major scale up and down
+fn synthetic(seconds: f32) -> Vec<f32> {
+    let scale = [60, 62, 64, 65, 67, 69, 71, 72, 71, 69, 67, 65, 64, 62];
+    let len = (seconds * SAMPLE_RATE) as usize;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE;
+            let note = scale[(t as usize) % scale.len()];
+            let freq = 440.0 * 2f32.powf((note - 69) as f32 / 12.0);
+            (1..=4)
+                .map(|h| (2.0 * PI * freq * h as f32 * t).sin() * 0.3 / h as f32)
+                .sum()
+        })
+        .collect()
+}
+
+// Run `f`, which processes `frames` frames, for at least a second and
+// print its throughput
+fn report(name: &str, frames: usize, seconds: f32, mut f: impl FnMut()) {
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs == 0 || start.elapsed().as_secs_f32() < 1.0 {
+        f();
+        runs += 1;
+    }
+    let elapsed = start.elapsed().as_secs_f32() / runs as f32;
+    println!(
+        "{:<28} {:>12.0} frames/s {:>10.0}x real time",
+        name,
+        frames as f32 / elapsed,
+        seconds / elapsed
+    );
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // cargo bench passes --bench, which is not ours
+    let arg = env::args().skip(1).find(|a| !a.starts_with("--"));
+    let (samples, sample_rate, source) = match arg {
+        Some(path) if path.ends_with(".wav") => {
+            let audio = decode::read_file(&path)?;
+            let signal = audio.signals(ChannelMode::Single(0))?.remove(0);
+            (signal.samples, audio.sample_rate(), path)
+        }
+        arg => {
+            let seconds = arg.map_or(Ok(600.0), |a| a.parse())?;
+            (
+                synthetic(seconds),
+                SAMPLE_RATE,
+                format!("{} s synthetic melody", seconds),
+            )
+        }
+    };
+    let seconds = samples.len() as f32 / sample_rate;
+    let config = AnalyzerConfig::default();
+    // The first frame needs fft_size samples, then one more every hop
+    let frames = samples
+        .len()
+        .checked_sub(config.fft_size)
+        .map_or(0, |rest| rest / config.hop_size + 1);
+    println!(
+        "{}: {:.0} s at {} Hz, fft {} hop {}, {} frames\n",
+        source, seconds, sample_rate, config.fft_size, config.hop_size, frames
+    );
+
+    // The bare transform, complex FFT of the zero imaginary signal against
+    // the packed real FFT
+    let size = config.fft_size;
+    let starts: Vec<usize> = (0..(samples.len() + 1).saturating_sub(size))
+        .step_by(config.hop_size)
+        .collect();
+    let mut planner = FftPlanner::new();
+    let complex = planner.plan_fft_forward(size);
+    let mut buffer = vec![Complex::new(0.0, 0.0); size];
+    let mut scratch = vec![Complex::new(0.0, 0.0); complex.get_inplace_scratch_len()];
+    report("fft complex", starts.len(), seconds, || {
+        for &start in &starts {
+            for (bin, &s) in buffer.iter_mut().zip(&samples[start..start + size]) {
+                *bin = Complex::new(s, 0.0);
+            }
+            complex.process_with_scratch(&mut buffer, &mut scratch);
+            black_box(&buffer);
+        }
+    });
+    let real = RealFft::new(size, &mut planner);
+    let mut output = real.make_output();
+    let mut scratch = real.make_scratch();
+    report("fft real", starts.len(), seconds, || {
+        for &start in &starts {
+            real.process(&samples[start..start + size], &mut output, &mut scratch);
+            black_box(&output);
+        }
+    });
+
+    for detector in [
+        DetectorKind::Peak,
+        DetectorKind::Yin,
+        DetectorKind::Mpm,
+        DetectorKind::Hps,
+        DetectorKind::Cepstrum,
+    ] {
+        let analyzer = Analyzer::new(AnalyzerConfig {
+            detector,
+            ..AnalyzerConfig::default()
+        });
+        let name = format!("analyze {}", detector);
+        report(&name, frames, seconds, || {
+            black_box(analyzer.analyze(&samples, sample_rate));
+        });
+    }
+    let threads = batch::default_threads();
+    let analyzer = Analyzer::new(AnalyzerConfig {
+        threads,
+        ..AnalyzerConfig::default()
+    });
+    let name = format!("analyze peak (threads: {})", threads);
+    report(&name, frames, seconds, || {
+        black_box(analyzer.analyze(&samples, sample_rate));
+    });
+    let analyzer = Analyzer::new(AnalyzerConfig {
+        polyphony: 4,
+        ..AnalyzerConfig::default()
+    });
+    report("analyze polyphony 4", frames, seconds, || {
+        black_box(analyzer.analyze(&samples, sample_rate));
+    });
+    let analyzer = Analyzer::new(AnalyzerConfig::default());
+    report("chromagram", frames, seconds, || {
+        black_box(analyzer.chromagram(&samples, sample_rate, &ChromaConfig::default()));
+    });
+    Ok(())
+}
@@ -0,0 +1,697 @@
+use crate::chroma::{ChromaConfig, Chromagram, chroma_vector};
+use crate::fft::RealFft;
+use crate::interpolate::Interpolation;
+use crate::notes::{A4, cents_from_note, freq_to_midi_note};
+use crate::onset::{Onset, OnsetDetector, OnsetFeatures, OnsetMethod, PeakPicker};
+use crate::pitch::{
+    DetectorKind, FrameInput, PitchDetector, PolyPitch, Polyphonic, main_lobe_bins,
+};
+use crate::window::WindowKind;
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::collections::HashMap;
+use std::thread;
+
+// Frames each thread takes from a batch. Due frames are gathered until
+// every thread has this many, bounding the samples held back.
+const FRAMES_PER_THREAD: usize = 16;
+
+// Parameters of the sliding window analysis
+#[derive(Debug, Clone)]
+pub struct AnalyzerConfig {
+    // Samples per analysis frame
+    pub fft_size: usize,
+    pub hop_size: usize,
+    // The FFT runs over fft_size * zero_padding points, the frame padded
+    // with zeros, for a finer interpolated spectrum
+    pub zero_padding: usize,
+    pub window: WindowKind,
+    // Frames below this frequency or peak magnitude count as silence
+    pub min_freq: f32,
+    pub min_magnitude: f32,
+    pub interpolation: Interpolation,
+    pub detector: DetectorKind,
+    // Maximum simultaneous notes per frame; above 1 the polyphonic
+    // estimator replaces the pitch detector
+    pub polyphony: usize,
+    pub onset_method: OnsetMethod,
+    // Frequency of A4 in Hz that notes and cents are measured against
+    pub reference: f32,
+    // Threads analyzing the frames of one signal; results are the same
+    // for any number
+    pub threads: usize,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        AnalyzerConfig {
+            fft_size: 4096,
+            hop_size: 2048,
+            zero_padding: 1,
+            window: WindowKind::default(),
+            min_freq: 20.0,
+            min_magnitude: 0.01,
+            interpolation: Interpolation::default(),
+            detector: DetectorKind::default(),
+            polyphony: 1,
+            onset_method: OnsetMethod::default(),
+            reference: A4,
+            threads: 1,
+        }
+    }
+}
+
+// One note sounding in a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Voice {
+    pub note: u8,
+    pub freq: f32,
+    pub cents: f32,
+    // Spectral magnitude behind this note
+    pub magnitude: f32,
+}
+
+// One voice per note, the strongest where several round to the same note,
+// in the order the notes first appear
+fn distinct_notes(voices: Vec<Voice>) -> Vec<Voice> {
+    let mut distinct: Vec<Voice> = Vec::with_capacity(voices.len());
+    for voice in voices {
+        match distinct.iter_mut().find(|v| v.note == voice.note) {
+            Some(kept) if voice.magnitude > kept.magnitude => *kept = voice,
+            Some(_) => {}
+            None => distinct.push(voice),
+        }
+    }
+    distinct
+}
+
+// Pitch detected in one FFT frame
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub index: usize,
+    // Centre of the frame in seconds, which is where a note that sets in
+    // there fills half the frame
+    pub time: f32,
+    // Fundamental frequency reported by the pitch detector
+    pub freq: f32,
+    // Largest magnitude in the frame's spectrum
+    pub magnitude: f32,
+    // Detector confidence in `freq`, 0 to 1
+    pub confidence: f32,
+    // None when the frame was rejected as noise or out of MIDI range
+    pub note: Option<u8>,
+    // Deviation of `freq` from `note` in cents, 0 for unvoiced frames
+    pub cents: f32,
+    // Every note accepted in the frame, the one in `note` first
+    pub voices: Vec<Voice>,
+    // Value of the onset detection function
+    pub onset_strength: f32,
+}
+
+// Result of analyzing one mono signal
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub sample_rate: f32,
+    // Samples between the starts of consecutive frames
+    pub hop_size: usize,
+    pub frames: Vec<Frame>,
+    pub onsets: Vec<Onset>,
+    // MIDI note -> number of frames it sounds in, most common first
+    pub note_counts: Vec<(u8, usize)>,
+}
+
+impl Analysis {
+    // Iterate over frames that produced a note
+    pub fn voiced_frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter().filter(|f| f.note.is_some())
+    }
+
+    // Mean frequency and cents deviation of the frames where `note` sounds
+    pub fn note_pitch(&self, note: u8) -> Option<(f32, f32)> {
+        let (mut freq, mut cents, mut count) = (0.0, 0.0, 0);
+        let voices = self.frames.iter().flat_map(|f| &f.voices);
+        for voice in voices.filter(|v| v.note == note) {
+            freq += voice.freq;
+            cents += voice.cents;
+            count += 1;
+        }
+        (count > 0).then(|| (freq / count as f32, cents / count as f32))
+    }
+}
+
+pub struct Analyzer {
+    config: AnalyzerConfig,
+    // Length of the transform including zero padding
+    fft_len: usize,
+    fft: RealFft,
+    window: Vec<f32>,
+}
+
+impl Analyzer {
+    pub fn new(config: AnalyzerConfig) -> Self {
+        let fft_len = config.fft_size * config.zero_padding.max(1);
+        let fft = RealFft::new(fft_len, &mut FftPlanner::new());
+        let window = config.window.generate(config.fft_size);
+        Analyzer {
+            config,
+            fft_len,
+            fft,
+            window,
+        }
+    }
+
+    pub fn config(&self) -> &AnalyzerConfig {
+        &self.config
+    }
+
+    // Run the sliding window FFT over a mono signal with the configured detector
+    pub fn analyze(&self, samples: &[f32], sample_rate: f32) -> Analysis {
+        let mut stream = self.stream(sample_rate);
+        let frames = stream.push(samples);
+        stream.into_analysis(frames)
+    }
+
+    // Run the sliding window FFT over a mono signal with any pitch detector
+    pub fn analyze_with(
+        &self,
+        samples: &[f32],
+        sample_rate: f32,
+        detector: &mut dyn PitchDetector,
+    ) -> Analysis {
+        let mut stream = self.stream_with(sample_rate, detector);
+        let frames = stream.push(samples);
+        stream.into_analysis(frames)
+    }
+
+    // Incremental analysis with the configured detector, for signals fed in
+    // blocks without holding all of them in memory
+    pub fn stream(&self, sample_rate: f32) -> AnalysisStream<'_> {
+        let estimators = (0..self.config.threads.max(1))
+            .map(|_| {
+                if self.config.polyphony > 1 {
+                    Estimator::Poly(Polyphonic::new(
+                        self.config.polyphony,
+                        self.config.interpolation,
+                        main_lobe_bins(self.config.window, self.config.zero_padding),
+                    ))
+                } else {
+                    Estimator::Owned(self.config.detector.build(
+                        self.config.fft_size,
+                        self.fft_len,
+                        self.config.interpolation,
+                    ))
+                }
+            })
+            .collect();
+        AnalysisStream::new(self, sample_rate, estimators)
+    }
+
+    // Incremental analysis with any pitch detector, on one thread as the
+    // detector cannot be shared
+    pub fn stream_with<'a>(
+        &'a self,
+        sample_rate: f32,
+        detector: &'a mut dyn PitchDetector,
+    ) -> AnalysisStream<'a> {
+        AnalysisStream::new(self, sample_rate, vec![Estimator::Borrowed(detector)])
+    }
+
+    // Pitch class profile of every frame, folded from the whole spectrum
+    pub fn chromagram(
+        &self,
+        samples: &[f32],
+        sample_rate: f32,
+        chroma: &ChromaConfig,
+    ) -> Chromagram {
+        let fft_size = self.config.fft_size;
+        let hop_size = self.config.hop_size.max(1);
+        let mut buffers = Buffers::new(self);
+        let mut mags = vec![0.0; self.fft_len / 2];
+        let mut frames = Vec::new();
+        let mut energy = Vec::new();
+
+        for start in (0..(samples.len() + 1).saturating_sub(fft_size)).step_by(hop_size) {
+            self.transform(&samples[start..start + fft_size], &mut buffers);
+            for (mag, bin) in mags.iter_mut().zip(&buffers.spectrum) {
+                *mag = bin.norm();
+            }
+            let (vector, total) = chroma_vector(
+                &mags,
+                sample_rate,
+                self.fft_len,
+                self.config.reference,
+                chroma,
+            );
+            frames.push(vector);
+            energy.push(total);
+        }
+
+        Chromagram {
+            sample_rate,
+            fft_size,
+            hop_size,
+            frames,
+            energy,
+        }
+    }
+
+    // Window one frame, zero pad it to the transform length and take its FFT
+    fn transform(&self, frame: &[f32], buffers: &mut Buffers) {
+        for ((out, sample), weight) in buffers.windowed.iter_mut().zip(frame).zip(&self.window) {
+            *out = sample * weight;
+        }
+        self.fft.process(
+            &buffers.windowed,
+            &mut buffers.spectrum,
+            &mut buffers.scratch,
+        );
+    }
+}
+
+// Work space of the transform, allocated once per stream
+struct Buffers {
+    windowed: Vec<f32>,
+    // Bins 0 to fft_len / 2 of the spectrum
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Buffers {
+    fn new(analyzer: &Analyzer) -> Self {
+        Buffers {
+            windowed: vec![0.0; analyzer.config.fft_size],
+            spectrum: analyzer.fft.make_output(),
+            scratch: analyzer.fft.make_scratch(),
+        }
+    }
+}
+
+// Where the pitches of a frame come from
+enum Estimator<'a> {
+    Owned(Box<dyn PitchDetector>),
+    Borrowed(&'a mut dyn PitchDetector),
+    Poly(Polyphonic),
+}
+
+impl Estimator<'_> {
+    fn estimate(&mut self, input: &FrameInput) -> Vec<PolyPitch> {
+        let detector: &mut dyn PitchDetector = match self {
+            Estimator::Poly(poly) => return poly.detect(input),
+            Estimator::Owned(detector) => detector.as_mut(),
+            Estimator::Borrowed(detector) => &mut **detector,
+        };
+        detector
+            .detect(input)
+            .map(|pitch| PolyPitch {
+                pitch,
+                magnitude: input.harmonic_magnitude(pitch.freq),
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+// Per thread state for analyzing frames: transform buffers and a pitch
+// estimator of its own
+struct Worker<'a> {
+    buffers: Buffers,
+    estimator: Estimator<'a>,
+}
+
+// What a worker found in one frame, merged into the stream in frame order
+struct FrameResult {
+    samples: Vec<f32>,
+    mags: Vec<f32>,
+    max_mag: f32,
+    onset: OnsetFeatures,
+    estimates: Vec<PolyPitch>,
+}
+
+impl Worker<'_> {
+    // Everything about a frame that does not depend on the frames before it
+    fn analyze(&mut self, analyzer: &Analyzer, sample_rate: f32, samples: Vec<f32>) -> FrameResult {
+        let config = &analyzer.config;
+        let fft_len = analyzer.fft_len;
+        analyzer.transform(&samples, &mut self.buffers);
+        let spectrum = &self.buffers.spectrum[..fft_len / 2];
+        let onset = config.onset_method.features(spectrum);
+        let mags: Vec<f32> = spectrum.iter().map(|bin| bin.norm()).collect();
+        let max_mag = mags.iter().skip(1).copied().fold(0.0, f32::max);
+        let input = FrameInput {
+            samples: &samples,
+            spectrum: &mags,
+            sample_rate,
+            fft_size: fft_len,
+            min_freq: config.min_freq,
+        };
+        let estimates = self.estimator.estimate(&input);
+        FrameResult {
+            samples,
+            mags,
+            max_mag,
+            onset,
+            estimates,
+        }
+    }
+}
+
+// Sliding window analysis over samples that arrive in blocks. The newest
+// fft_size samples live in a ring buffer and a frame is analyzed every
+// hop_size samples, so memory stays bounded however long the signal is.
+// With several workers, due frames are gathered into batches analyzed in
+// parallel and merged back in time order, so results do not depend on the
+// number of threads.
+pub struct AnalysisStream<'a> {
+    analyzer: &'a Analyzer,
+    sample_rate: f32,
+    workers: Vec<Worker<'a>>,
+    ring: Vec<f32>,
+    // Next write position in `ring`, which is also its oldest sample
+    position: usize,
+    filled: usize,
+    // Samples still to come before the next frame is due
+    countdown: usize,
+    // Frames due but not analyzed yet, each the ring unrolled oldest first
+    pending: Vec<Vec<f32>>,
+    onset_detector: OnsetDetector,
+    peak_picker: PeakPicker,
+    index: usize,
+    onsets: Vec<Onset>,
+    // Frequency count map: MIDI note -> count
+    note_counts: HashMap<u8, usize>,
+}
+
+impl<'a> AnalysisStream<'a> {
+    fn new(analyzer: &'a Analyzer, sample_rate: f32, estimators: Vec<Estimator<'a>>) -> Self {
+        let fft_size = analyzer.config.fft_size;
+        AnalysisStream {
+            analyzer,
+            sample_rate,
+            workers: estimators
+                .into_iter()
+                .map(|estimator| Worker {
+                    buffers: Buffers::new(analyzer),
+                    estimator,
+                })
+                .collect(),
+            ring: vec![0.0; fft_size],
+            position: 0,
+            filled: 0,
+            countdown: fft_size,
+            pending: Vec::new(),
+            onset_detector: OnsetDetector::new(analyzer.config.onset_method),
+            peak_picker: PeakPicker::new(),
+            index: 0,
+            onsets: Vec::new(),
+            note_counts: HashMap::new(),
+        }
+    }
+
+    // Feed the next block of samples and return the frames it completed
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        self.push_with(samples, |frame, _| frames.push(frame));
+        frames
+    }
+
+    // Feed the next block of samples, handing every completed frame to
+    // `on_frame` along with the spectrum it was detected from
+    pub fn push_with<F>(&mut self, samples: &[f32], mut on_frame: F)
+    where
+        F: FnMut(Frame, &FrameInput),
+    {
+        let size = self.ring.len();
+        let hop_size = self.analyzer.config.hop_size.max(1);
+        let batch = match self.workers.len() {
+            1 => 1,
+            workers => workers * FRAMES_PER_THREAD,
+        };
+        for &sample in samples {
+            self.ring[self.position] = sample;
+            self.position = (self.position + 1) % size;
+            self.filled = (self.filled + 1).min(size);
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.countdown = hop_size;
+                if self.filled == size {
+                    let (older, newer) = self.ring.split_at(self.position);
+                    self.pending.push([newer, older].concat());
+                    if self.pending.len() >= batch {
+                        self.flush(&mut on_frame);
+                    }
+                }
+            }
+        }
+        self.flush(&mut on_frame);
+    }
+
+    // Onsets confirmed so far; each lags its frame by a few frames
+    pub fn onsets(&self) -> &[Onset] {
+        &self.onsets
+    }
+
+    // Finish the stream as an Analysis holding `frames`, which may be all
+    // the frames it produced or none when the caller kept them elsewhere
+    pub fn into_analysis(self, frames: Vec<Frame>) -> Analysis {
+        // Sort notes by count descending, ties by pitch so output is stable
+        let mut note_counts: Vec<(u8, usize)> = self.note_counts.into_iter().collect();
+        note_counts.sort_by_key(|&(note, count)| (std::cmp::Reverse(count), note));
+
+        Analysis {
+            sample_rate: self.sample_rate,
+            hop_size: self.analyzer.config.hop_size.max(1),
+            frames,
+            onsets: self.onsets,
+            note_counts,
+        }
+    }
+
+    // Analyze the pending frames, split into one contiguous run per worker,
+    // and merge the results in frame order
+    fn flush<F>(&mut self, on_frame: &mut F)
+    where
+        F: FnMut(Frame, &FrameInput),
+    {
+        let analyzer = self.analyzer;
+        let sample_rate = self.sample_rate;
+        let mut pending = std::mem::take(&mut self.pending).into_iter();
+        let results: Vec<FrameResult> = match self.workers.as_mut_slice() {
+            // One worker, or a single frame as when fed live
+            [worker, rest @ ..] if rest.is_empty() || pending.len() < 2 => pending
+                .map(|samples| worker.analyze(analyzer, sample_rate, samples))
+                .collect(),
+            workers => {
+                let run = pending.len().div_ceil(workers.len());
+                thread::scope(|scope| {
+                    let handles: Vec<_> = workers
+                        .iter_mut()
+                        .map(|worker| {
+                            let frames: Vec<Vec<f32>> = pending.by_ref().take(run).collect();
+                            scope.spawn(move || {
+                                frames
+                                    .into_iter()
+                                    .map(|samples| worker.analyze(analyzer, sample_rate, samples))
+                                    .collect::<Vec<_>>()
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .flat_map(|handle| handle.join().expect("frame analysis thread panicked"))
+                        .collect()
+                })
+            }
+        };
+        for result in results {
+            self.merge(result, on_frame);
+        }
+    }
+
+    // Centre of frame `index` in seconds
+    fn frame_time(&self, index: usize) -> f32 {
+        let config = &self.analyzer.config;
+        (index * config.hop_size.max(1) + config.fft_size / 2) as f32 / self.sample_rate
+    }
+
+    // The part of a frame's analysis that depends on the frames before it
+    fn merge<F>(&mut self, result: FrameResult, on_frame: &mut F)
+    where
+        F: FnMut(Frame, &FrameInput),
+    {
+        let config = &self.analyzer.config;
+        let onset_strength = self.onset_detector.push(result.onset);
+        if let Some((frame, strength)) = self.peak_picker.push(onset_strength) {
+            self.onsets.push(Onset {
+                frame,
+                time: self.frame_time(frame),
+                strength,
+            });
+        }
+
+        // Filter out very low frequencies and low magnitude noise
+        let estimates = &result.estimates;
+        let voices = if result.max_mag < config.min_magnitude {
+            Vec::new()
+        } else {
+            let voices = estimates
+                .iter()
+                .filter(|e| e.pitch.freq >= config.min_freq && e.magnitude >= config.min_magnitude)
+                .filter_map(|e| {
+                    let note = freq_to_midi_note(e.pitch.freq, config.reference)?;
+                    Some(Voice {
+                        note,
+                        freq: e.pitch.freq,
+                        cents: cents_from_note(e.pitch.freq, note, config.reference),
+                        magnitude: e.magnitude,
+                    })
+                })
+                .collect();
+            distinct_notes(voices)
+        };
+
+        // Count every sounding note
+        for voice in &voices {
+            *self.note_counts.entry(voice.note).or_insert(0) += 1;
+        }
+
+        let (freq, confidence) = estimates
+            .first()
+            .map_or((0.0, 0.0), |e| (e.pitch.freq, e.pitch.confidence));
+        let primary = voices.first();
+        let frame = Frame {
+            index: self.index,
+            time: self.frame_time(self.index),
+            freq: primary.map_or(freq, |v| v.freq),
+            magnitude: result.max_mag,
+            confidence,
+            note: primary.map(|v| v.note),
+            cents: primary.map_or(0.0, |v| v.cents),
+            voices,
+            onset_strength,
+        };
+        self.index += 1;
+        let input = FrameInput {
+            samples: &result.samples,
+            spectrum: &result.mags,
+            sample_rate: self.sample_rate,
+            fft_size: self.analyzer.fft_len,
+            min_freq: config.min_freq,
+        };
+        on_frame(frame, &input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voice(note: u8, magnitude: f32) -> Voice {
+        Voice {
+            note,
+            freq: 440.0,
+            cents: 0.0,
+            magnitude,
+        }
+    }
+
+    #[test]
+    fn one_voice_per_note() {
+        let voices = vec![
+            voice(60, 5.0),
+            voice(64, 2.0),
+            voice(60, 7.0),
+            voice(64, 1.0),
+        ];
+        assert_eq!(distinct_notes(voices), [voice(60, 7.0), voice(64, 2.0)]);
+    }
+
+    #[test]
+    fn times_are_frame_centres() {
+        let sample_rate = 8000.0;
+        let config = AnalyzerConfig {
+            fft_size: 1024,
+            hop_size: 256,
+            ..AnalyzerConfig::default()
+        };
+        // Half a second of silence, then a second of A4
+        let samples: Vec<f32> = (0..12000)
+            .map(|i| match i {
+                0..4000 => 0.0,
+                _ => (std::f32::consts::TAU * 440.0 * i as f32 / sample_rate).sin() * 0.5,
+            })
+            .collect();
+        let analysis = Analyzer::new(config).analyze(&samples, sample_rate);
+        // Frames start every hop and are stamped with their middle sample
+        assert_eq!(analysis.frames.len(), 43);
+        for frame in &analysis.frames {
+            assert_eq!(frame.time, (frame.index * 256 + 512) as f32 / sample_rate);
+        }
+        // so the onset lands within a hop of the true start
+        assert_eq!(analysis.onsets.len(), 1);
+        let onset = &analysis.onsets[0];
+        assert_eq!(onset.time, analysis.frames[onset.frame].time);
+        assert!(
+            (onset.time - 0.5).abs() <= 256.0 / sample_rate,
+            "{}",
+            onset.time
+        );
+    }
+
+    // Everything but the sample rate and hop, which come from the config
+    fn summary(analysis: &Analysis) -> String {
+        format!(
+            "{:?} {:?} {:?}",
+            analysis.frames, analysis.onsets, analysis.note_counts
+        )
+    }
+
+    #[test]
+    fn threads_agree() {
+        // A note every 0.3 s with a few harmonics, long enough for several
+        // batches of frames per thread
+        let sample_rate = 8000.0;
+        let samples: Vec<f32> = (0..32000)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let freq = 220.0 * 2f32.powf(((t / 0.3) as usize % 7) as f32 / 12.0);
+                (1..=3)
+                    .map(|h| (std::f32::consts::TAU * freq * h as f32 * t).sin() * 0.3 / h as f32)
+                    .sum()
+            })
+            .collect();
+        for polyphony in [1, 3] {
+            let config = AnalyzerConfig {
+                fft_size: 512,
+                hop_size: 128,
+                polyphony,
+                ..AnalyzerConfig::default()
+            };
+            let single = Analyzer::new(config.clone()).analyze(&samples, sample_rate);
+            assert!(single.frames.len() > 4 * FRAMES_PER_THREAD);
+            assert!(single.onsets.len() > 5);
+            let expected = summary(&single);
+            for threads in [2, 3] {
+                let analyzer = Analyzer::new(AnalyzerConfig {
+                    threads,
+                    ..config.clone()
+                });
+                let whole = analyzer.analyze(&samples, sample_rate);
+                assert_eq!(summary(&whole), expected, "{} threads", threads);
+
+                // Blocks that split frames and batches anywhere
+                let mut stream = analyzer.stream(sample_rate);
+                let mut frames = Vec::new();
+                let mut rest = samples.as_slice();
+                for size in [1, 7, 333, 1000, 129, 4097].iter().cycle() {
+                    if rest.is_empty() {
+                        break;
+                    }
+                    let (block, tail) = rest.split_at((*size).min(rest.len()));
+                    frames.extend(stream.push(block));
+                    rest = tail;
+                }
+                let streamed = stream.into_analysis(frames);
+                assert_eq!(summary(&streamed), expected, "{} threads streamed", threads);
+            }
+        }
+    }
+}
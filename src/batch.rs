@@ -0,0 +1,348 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// An input that could not be turned into files
+#[derive(Debug)]
+pub struct InputError {
+    pub input: String,
+    pub error: io::Error,
+}
+
+// Resolve command line inputs into WAV files. Plain paths are taken as is,
+// directories are searched recursively and patterns containing *, ? or [
+// are expanded as globs, with ** matching any number of directories.
+pub fn expand_inputs(inputs: &[String]) -> (Vec<PathBuf>, Vec<InputError>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for input in inputs {
+        let found = if is_glob(input) {
+            expand_glob(input).and_then(|found| {
+                if found.is_empty() {
+                    Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "pattern matched no WAV files",
+                    ))
+                } else {
+                    Ok(found)
+                }
+            })
+        } else {
+            let path = Path::new(input);
+            if path.is_dir() {
+                let mut found = Vec::new();
+                walk(path, None, false, &mut found).map(|_| found)
+            } else {
+                Ok(vec![path.to_path_buf()])
+            }
+        };
+        match found {
+            Ok(mut found) => {
+                found.sort();
+                files.extend(found);
+            }
+            Err(error) => errors.push(InputError {
+                input: input.clone(),
+                error,
+            }),
+        }
+    }
+    // The same file named twice, e.g. by a directory and a glob, runs once
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+    (files, errors)
+}
+
+pub fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav") || ext.eq_ignore_ascii_case("wave"))
+}
+
+// Collect every WAV file below a directory, descending at most `depth`
+// levels into subdirectories (no limit for None). Symlinked directories are
+// not followed, so a link back up the tree cannot loop; with
+// `skip_unreadable` subdirectories that cannot be listed are passed over.
+fn walk(
+    dir: &Path,
+    depth: Option<usize>,
+    skip_unreadable: bool,
+    found: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if depth == Some(0) {
+                continue;
+            }
+            match walk(&path, depth.map(|d| d - 1), skip_unreadable, found) {
+                Err(_) if skip_unreadable => {}
+                result => result?,
+            }
+        } else if is_wav(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn expand_glob(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    // Walk from the longest leading part of the pattern without wildcards
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in Path::new(pattern).components() {
+        let text = component.as_os_str().to_string_lossy().into_owned();
+        if rest.is_empty() && !is_glob(&text) {
+            base.push(component);
+        } else if !matches!(component, Component::CurDir) {
+            rest.push(text);
+        }
+    }
+    let root = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base.clone()
+    };
+
+    // Without ** the matches lie exactly as deep as the pattern is long
+    let depth = if rest.iter().any(|c| c == "**") {
+        None
+    } else {
+        Some(rest.len().saturating_sub(1))
+    };
+    let mut candidates = Vec::new();
+    walk(&root, depth, true, &mut candidates)?;
+    Ok(candidates
+        .into_iter()
+        .filter(|path| {
+            let relative: Vec<String> = path
+                .strip_prefix(&root)
+                .unwrap_or(path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            match_components(&rest, &relative)
+        })
+        .map(|path| {
+            // Report paths the way the pattern spelled them, without a ./ prefix
+            if base.as_os_str().is_empty() {
+                path.strip_prefix(".")
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path)
+            } else {
+                path
+            }
+        })
+        .collect())
+}
+
+// Match path components against pattern components, ** spanning any number
+fn match_components(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_components(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, tail)| glob_match(first, name) && match_components(rest, tail)),
+    }
+}
+
+// Match one file name against a pattern with *, ? and [abc] / [a-z] / [!a]
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern
+                .iter()
+                .skip(2)
+                .position(|&c| c == ']')
+                .map(|p| p + 2)
+            else {
+                // No closing bracket, treat [ literally
+                return name.first() == Some(&'[') && match_from(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let (negate, set) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..close]),
+                _ => (false, &pattern[1..close]),
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            found != negate && match_from(&pattern[close + 1..], &name[1..])
+        }
+        Some(&literal) => name.first() == Some(&literal) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+// Number of worker threads to use when none is configured
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Apply `f` to every item on up to `threads` worker threads. Results come
+// back in the order of `items`, whatever order the work finished in.
+pub fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every item is processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch directory under the system temp dir, removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn touch(&self, path: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"").unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn glob_names() {
+        let cases = [
+            ("*.wav", "take1.wav", true),
+            ("*.wav", "take1.wave", false),
+            ("take?.wav", "take1.wav", true),
+            ("take?.wav", "take10.wav", false),
+            ("take[0-9].wav", "take7.wav", true),
+            ("take[!0-9].wav", "take7.wav", false),
+            ("take[!0-9].wav", "takeA.wav", true),
+            ("[ab]*", "bass.wav", true),
+            ("[ab]*", "cello.wav", false),
+            ("a[b", "a[b", true),
+            ("*", "", true),
+            ("?", "", false),
+        ];
+        for (pattern, name, matches) in cases {
+            assert_eq!(glob_match(pattern, name), matches, "{} ~ {}", pattern, name);
+        }
+    }
+
+    #[test]
+    fn glob_components() {
+        let split = |s: &str| -> Vec<String> { s.split('/').map(str::to_string).collect() };
+        let cases = [
+            ("**/*.wav", "a.wav", true),
+            ("**/*.wav", "x/y/a.wav", true),
+            ("x/**/a.wav", "x/a.wav", true),
+            ("x/**/a.wav", "x/y/z/a.wav", true),
+            ("x/**/a.wav", "y/a.wav", false),
+            ("*/a.wav", "x/a.wav", true),
+            ("*/a.wav", "x/y/a.wav", false),
+            ("*.wav", "x/a.wav", false),
+        ];
+        for (pattern, path, matches) in cases {
+            assert_eq!(
+                match_components(&split(pattern), &split(path)),
+                matches,
+                "{} ~ {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_not_followed() {
+        let dir = TempDir::new("wav-walk-loop");
+        let a = dir.touch("a.wav");
+        let b = dir.touch("sub/b.wav");
+        std::os::unix::fs::symlink(&dir.0, dir.0.join("sub/loop")).unwrap();
+        let mut found = Vec::new();
+        walk(&dir.0, None, false, &mut found).unwrap();
+        found.sort();
+        assert_eq!(found, [a, b]);
+    }
+
+    #[test]
+    fn glob_depth() {
+        let dir = TempDir::new("wav-glob-depth");
+        let top = dir.touch("x.wav");
+        let one = dir.touch("d/y.wav");
+        let two = dir.touch("d/e/z.wav");
+        let expand = |pattern: &str| {
+            let mut found = expand_glob(&format!("{}/{}", dir.0.display(), pattern)).unwrap();
+            found.sort();
+            found
+        };
+        assert_eq!(expand("*.wav"), vec![top.clone()]);
+        assert_eq!(expand("*/*.wav"), vec![one.clone()]);
+        assert_eq!(expand("**/*.wav"), [two, one, top]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// Which channel(s) of a multichannel file get analyzed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    // Average all channels into a single mono signal
+    Downmix,
+    // Analyze one channel by zero-based index
+    Single(usize),
+    // Analyze every channel separately
+    Each,
+}
+
+impl FromStr for ChannelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mix" | "downmix" | "mono" => Ok(ChannelMode::Downmix),
+            "all" | "each" => Ok(ChannelMode::Each),
+            "left" | "l" => Ok(ChannelMode::Single(0)),
+            "right" | "r" => Ok(ChannelMode::Single(1)),
+            _ => s.parse().map(ChannelMode::Single).map_err(|_| {
+                format!(
+                    "invalid channel mode '{}' (expected mix, all, left, right or an index)",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ChannelMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelMode::Downmix => f.write_str("mix"),
+            ChannelMode::Single(index) => write!(f, "{}", index),
+            ChannelMode::Each => f.write_str("all"),
+        }
+    }
+}
+
+// One mono signal to analyze, labelled with where it came from
+pub struct Signal {
+    pub label: String,
+    pub samples: Vec<f32>,
+}
+
+impl Signal {
+    fn new(label: impl Into<String>, samples: Vec<f32>) -> Self {
+        Signal {
+            label: label.into(),
+            samples,
+        }
+    }
+}
+
+// Split interleaved samples into one vector per channel
+pub fn deinterleave(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    let frames = samples.len() / channels.max(1);
+    let mut out = vec![Vec::with_capacity(frames); channels];
+    for frame in samples.chunks_exact(channels) {
+        for (channel, &sample) in out.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+    out
+}
+
+// Average every channel into one mono signal
+pub fn downmix(channels: &[Vec<f32>]) -> Vec<f32> {
+    let len = channels.iter().map(Vec::len).min().unwrap_or(0);
+    let scale = 1.0 / channels.len().max(1) as f32;
+    (0..len)
+        .map(|i| channels.iter().map(|c| c[i]).sum::<f32>() * scale)
+        .collect()
+}
+
+// Turn interleaved samples into the labelled signals selected by `mode`
+pub fn select(
+    samples: &[f32],
+    channels: usize,
+    mode: ChannelMode,
+) -> Result<Vec<Signal>, Box<dyn Error>> {
+    let mut split = deinterleave(samples, channels);
+    match mode {
+        ChannelMode::Downmix if channels == 1 => Ok(vec![Signal::new("mono", split.remove(0))]),
+        ChannelMode::Downmix => Ok(vec![Signal::new("downmix", downmix(&split))]),
+        ChannelMode::Single(index) if index < channels => Ok(vec![Signal::new(
+            format!("channel {}", index),
+            split.swap_remove(index),
+        )]),
+        ChannelMode::Single(index) => Err(format!(
+            "channel {} requested but the file has {} channel(s)",
+            index, channels
+        )
+        .into()),
+        ChannelMode::Each => Ok(split
+            .into_iter()
+            .enumerate()
+            .map(|(index, signal)| Signal::new(format!("channel {}", index), signal))
+            .collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEREO: [f32; 6] = [0.5, -0.5, 1.0, 0.0, 0.25, 0.75];
+
+    fn labels(signals: &[Signal]) -> Vec<&str> {
+        signals.iter().map(|s| s.label.as_str()).collect()
+    }
+
+    #[test]
+    fn deinterleave_channels() {
+        assert_eq!(
+            deinterleave(&STEREO, 2),
+            [vec![0.5, 1.0, 0.25], vec![-0.5, 0.0, 0.75]]
+        );
+        assert_eq!(
+            deinterleave(&STEREO, 3),
+            [vec![0.5, 0.0], vec![-0.5, 0.25], vec![1.0, 0.75]]
+        );
+        // A trailing partial frame is dropped
+        assert_eq!(
+            deinterleave(&STEREO[..5], 2),
+            [vec![0.5, 1.0], vec![-0.5, 0.0]]
+        );
+    }
+
+    #[test]
+    fn downmix_averages() {
+        assert_eq!(downmix(&deinterleave(&STEREO, 2)), [0.0, 0.5, 0.5]);
+        assert!(downmix(&[]).is_empty());
+    }
+
+    #[test]
+    fn select_modes() {
+        let mixed = select(&STEREO, 2, ChannelMode::Downmix).unwrap();
+        assert_eq!(labels(&mixed), ["downmix"]);
+        assert_eq!(mixed[0].samples, [0.0, 0.5, 0.5]);
+
+        let mono = select(&STEREO, 1, ChannelMode::Downmix).unwrap();
+        assert_eq!(labels(&mono), ["mono"]);
+        assert_eq!(mono[0].samples, STEREO);
+
+        let right = select(&STEREO, 2, ChannelMode::Single(1)).unwrap();
+        assert_eq!(labels(&right), ["channel 1"]);
+        assert_eq!(right[0].samples, [-0.5, 0.0, 0.75]);
+
+        let each = select(&STEREO, 2, ChannelMode::Each).unwrap();
+        assert_eq!(labels(&each), ["channel 0", "channel 1"]);
+        assert_eq!(each[0].samples, [0.5, 1.0, 0.25]);
+
+        assert!(select(&STEREO, 2, ChannelMode::Single(2)).is_err());
+    }
+
+    #[test]
+    fn parse_modes() {
+        for (text, mode) in [
+            ("mix", ChannelMode::Downmix),
+            ("all", ChannelMode::Each),
+            ("left", ChannelMode::Single(0)),
+            ("r", ChannelMode::Single(1)),
+            ("3", ChannelMode::Single(3)),
+        ] {
+            assert_eq!(text.parse::<ChannelMode>(), Ok(mode));
+        }
+        assert!("center".parse::<ChannelMode>().is_err());
+    }
+}
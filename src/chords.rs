@@ -0,0 +1,275 @@
+use crate::chroma::Chromagram;
+use crate::notes::pitch_class_name;
+use std::fmt;
+
+// Probability that the chord stays the same from one frame to the next
+const STAY: f32 = 0.95;
+// Scales template similarity into a log likelihood; higher follows the
+// frame by frame best match more closely
+const SHARPNESS: f32 = 20.0;
+// Similarity of the no-chord state for frames with sound in them, just
+// above what a lone pitch class scores against a triad (1/sqrt(3))
+const NO_CHORD_SCORE: f32 = 0.6;
+// Frames this far below the loudest frame (energy ratio, -40 dB) are silence
+const SILENCE: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Dominant7,
+    Major7,
+    Minor7,
+    HalfDiminished7,
+    Diminished7,
+}
+
+impl Quality {
+    pub const ALL: [Quality; 11] = [
+        Quality::Major,
+        Quality::Minor,
+        Quality::Diminished,
+        Quality::Augmented,
+        Quality::Sus2,
+        Quality::Sus4,
+        Quality::Dominant7,
+        Quality::Major7,
+        Quality::Minor7,
+        Quality::HalfDiminished7,
+        Quality::Diminished7,
+    ];
+
+    // Semitones above the root
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            Quality::Major => &[0, 4, 7],
+            Quality::Minor => &[0, 3, 7],
+            Quality::Diminished => &[0, 3, 6],
+            Quality::Augmented => &[0, 4, 8],
+            Quality::Sus2 => &[0, 2, 7],
+            Quality::Sus4 => &[0, 5, 7],
+            Quality::Dominant7 => &[0, 4, 7, 10],
+            Quality::Major7 => &[0, 4, 7, 11],
+            Quality::Minor7 => &[0, 3, 7, 10],
+            Quality::HalfDiminished7 => &[0, 3, 6, 10],
+            Quality::Diminished7 => &[0, 3, 6, 9],
+        }
+    }
+}
+
+impl fmt::Display for Quality {
+    // Shorthands of Harte et al.'s chord syntax
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Quality::Major => "maj",
+            Quality::Minor => "min",
+            Quality::Diminished => "dim",
+            Quality::Augmented => "aug",
+            Quality::Sus2 => "sus2",
+            Quality::Sus4 => "sus4",
+            Quality::Dominant7 => "7",
+            Quality::Major7 => "maj7",
+            Quality::Minor7 => "min7",
+            Quality::HalfDiminished7 => "hdim7",
+            Quality::Diminished7 => "dim7",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    // Pitch class of the root, 0 is C
+    pub root: u8,
+    pub quality: Quality,
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", pitch_class_name(self.root), self.quality)
+    }
+}
+
+// A stretch of time with one chord, None for no chord
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChordSegment {
+    pub chord: Option<Chord>,
+    // Start and end in seconds
+    pub start: f32,
+    pub end: f32,
+}
+
+impl ChordSegment {
+    // Chord symbol, N for no chord
+    pub fn label(&self) -> String {
+        self.chord.map_or("N".to_string(), |c| c.to_string())
+    }
+}
+
+// Every chord the recognizer can output, in state order after no-chord
+fn chords() -> Vec<Chord> {
+    Quality::ALL
+        .iter()
+        .flat_map(|&quality| (0..12).map(move |root| Chord { root, quality }))
+        .collect()
+}
+
+// Cosine similarity of a chroma vector and a chord's binary template
+fn similarity(chroma: &[f32; 12], chord: Chord) -> f32 {
+    let norm = chroma.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return 0.0;
+    }
+    let intervals = chord.quality.intervals();
+    let dot: f32 = intervals
+        .iter()
+        .map(|&i| chroma[((chord.root + i) % 12) as usize])
+        .sum();
+    dot / (norm * (intervals.len() as f32).sqrt())
+}
+
+// Chord timeline of a chromagram. Every frame is matched against the chord
+// templates and the Viterbi path through an HMM that favours staying on the
+// same chord smooths the frame by frame matches.
+pub fn recognize(chromagram: &Chromagram) -> Vec<ChordSegment> {
+    let frames = chromagram.frames.len();
+    if frames == 0 {
+        return Vec::new();
+    }
+    let chords = chords();
+    // State 0 is no chord, state i > 0 is chords[i - 1]
+    let states = chords.len() + 1;
+    let loudest = chromagram.energy.iter().copied().fold(0.0, f32::max);
+
+    let emission = |t: usize, out: &mut Vec<f32>| {
+        out.clear();
+        if chromagram.energy[t] <= loudest * SILENCE {
+            out.push(SHARPNESS);
+            out.extend(chords.iter().map(|_| 0.0));
+        } else {
+            out.push(SHARPNESS * NO_CHORD_SCORE);
+            let chroma = &chromagram.frames[t];
+            out.extend(chords.iter().map(|&c| SHARPNESS * similarity(chroma, c)));
+        }
+    };
+
+    let stay = STAY.ln();
+    let switch = ((1.0 - STAY) / (states - 1) as f32).ln();
+    let mut scores = Vec::with_capacity(states);
+    emission(0, &mut scores);
+    let mut back: Vec<Vec<u16>> = Vec::with_capacity(frames);
+    let mut observed = Vec::with_capacity(states);
+    for t in 1..frames {
+        // Switching costs the same from every state, so only the best
+        // previous state competes with staying put
+        let (best, &best_score) = scores
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap_or((0, &0.0));
+        emission(t, &mut observed);
+        let mut pointers = Vec::with_capacity(states);
+        for (state, score) in scores.iter_mut().enumerate() {
+            let (from, prior) = if *score + stay >= best_score + switch {
+                (state, *score + stay)
+            } else {
+                (best, best_score + switch)
+            };
+            pointers.push(from as u16);
+            *score = prior + observed[state];
+        }
+        back.push(pointers);
+    }
+
+    // Trace the best path back from the most likely final state
+    let mut state = scores
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map_or(0, |(i, _)| i);
+    let mut path = vec![0; frames];
+    for t in (0..frames).rev() {
+        path[t] = state;
+        if t > 0 {
+            state = back[t - 1][state] as usize;
+        }
+    }
+
+    // Merge runs of the same state into segments
+    let mut segments: Vec<ChordSegment> = Vec::new();
+    for (t, &state) in path.iter().enumerate() {
+        let chord = (state > 0).then(|| chords[state - 1]);
+        match segments.last_mut() {
+            Some(last) if last.chord == chord => last.end = chromagram.time(t + 1),
+            _ => segments.push(ChordSegment {
+                chord,
+                start: chromagram.time(t),
+                end: chromagram.time(t + 1),
+            }),
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chroma with the given pitch classes at full strength
+    fn chroma(classes: &[usize]) -> [f32; 12] {
+        let mut chroma = [0.0; 12];
+        for &pc in classes {
+            chroma[pc] = 1.0;
+        }
+        chroma
+    }
+
+    #[test]
+    fn templates() {
+        let c_major = chroma(&[0, 4, 7]);
+        let best = chords()
+            .into_iter()
+            .max_by(|a, b| similarity(&c_major, *a).total_cmp(&similarity(&c_major, *b)));
+        assert_eq!(best.map(|c| c.to_string()), Some("C:maj".to_string()));
+        let c = Chord {
+            root: 0,
+            quality: Quality::Major,
+        };
+        assert!((similarity(&c_major, c) - 1.0).abs() < 1e-6);
+        assert_eq!(similarity(&[0.0; 12], c), 0.0);
+    }
+
+    #[test]
+    fn smoothed_timeline() {
+        // C major with a one frame E minor glitch, G major, then silence;
+        // frames are 0.1 s apart and 0.1 s long
+        let mut frames = vec![chroma(&[0, 4, 7]); 10];
+        frames[5] = chroma(&[4, 7, 11]);
+        frames.extend([chroma(&[7, 11, 2]); 10]);
+        let mut energy = vec![1.0; frames.len()];
+        frames.extend([[0.0; 12]; 5]);
+        energy.extend([0.0; 5]);
+        let chromagram = Chromagram {
+            sample_rate: 100.0,
+            fft_size: 10,
+            hop_size: 10,
+            frames,
+            energy,
+        };
+        let segments: Vec<(String, f32, f32)> = recognize(&chromagram)
+            .iter()
+            .map(|s| (s.label(), s.start, s.end))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                ("C:maj".to_string(), 0.05, 1.05),
+                ("G:maj".to_string(), 1.05, 2.05),
+                ("N".to_string(), 2.05, 2.55),
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::notes::freq_to_midi;
+use crate::png::Image;
+use std::f32::consts::PI;
+
+// Heat map pixels per frame horizontally and per pitch class vertically
+const CELL_WIDTH: usize = 2;
+const CELL_HEIGHT: usize = 12;
+// Gap between the heat maps of consecutive signals
+const SEPARATOR: usize = 4;
+
+// Which part of the spectrum is folded into the 12 pitch classes
+#[derive(Debug, Clone, Copy)]
+pub struct ChromaConfig {
+    // Octaves in scientific pitch notation, C of min_octave to B of
+    // max_octave inclusive (C4 is middle C)
+    pub min_octave: i32,
+    pub max_octave: i32,
+}
+
+impl Default for ChromaConfig {
+    fn default() -> Self {
+        ChromaConfig {
+            min_octave: 2,
+            max_octave: 7,
+        }
+    }
+}
+
+// 12-bin pitch class energy per frame
+#[derive(Debug, Clone)]
+pub struct Chromagram {
+    pub sample_rate: f32,
+    // Samples per frame and between the starts of consecutive frames
+    pub fft_size: usize,
+    pub hop_size: usize,
+    // Pitch classes C to B per frame, scaled so the strongest is 1
+    pub frames: Vec<[f32; 12]>,
+    // Spectral energy folded into each frame before scaling
+    pub energy: Vec<f32>,
+}
+
+impl Chromagram {
+    // Centre of frame `index` in seconds, as for the frames of an Analysis
+    pub fn time(&self, index: usize) -> f32 {
+        (index * self.hop_size + self.fft_size / 2) as f32 / self.sample_rate
+    }
+}
+
+// Fold a magnitude spectrum into pitch classes. Each bin adds its energy to
+// the nearest semitone, weighted down the further it lies from that
+// semitone's centre under the given A4 reference. Returns the scaled chroma
+// and the total energy folded into it.
+pub fn chroma_vector(
+    spectrum: &[f32],
+    sample_rate: f32,
+    fft_size: usize,
+    reference: f32,
+    config: &ChromaConfig,
+) -> ([f32; 12], f32) {
+    // MIDI numbers of the lowest and highest notes, half a semitone wider
+    let low = 12.0 * (config.min_octave + 1) as f32 - 0.5;
+    let high = 12.0 * (config.max_octave + 1) as f32 + 11.5;
+
+    let mut chroma = [0.0; 12];
+    for (bin, &mag) in spectrum.iter().enumerate().skip(1) {
+        let freq = bin as f32 * sample_rate / fft_size as f32;
+        let midi = freq_to_midi(freq, reference);
+        if midi < low || midi > high {
+            continue;
+        }
+        let nearest = midi.round();
+        let weight = (PI * (midi - nearest)).cos().powi(2);
+        chroma[(nearest as i32).rem_euclid(12) as usize] += weight * mag * mag;
+    }
+
+    let energy = chroma.iter().sum();
+    let max = chroma.iter().copied().fold(0.0, f32::max);
+    if max > 0.0 {
+        for value in chroma.iter_mut() {
+            *value /= max;
+        }
+    }
+    (chroma, energy)
+}
+
+// Black through red and yellow to white for values from 0 to 1
+fn heat_color(value: f32) -> [u8; 3] {
+    let v = value.clamp(0.0, 1.0) * 3.0;
+    let channel = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(v), channel(v - 1.0), channel(v - 2.0)]
+}
+
+// Heat map of one or more chromagrams stacked top to bottom, time running
+// left to right and B at the top of each
+pub fn heat_map(chromagrams: &[&Chromagram]) -> Image {
+    let frames = chromagrams
+        .iter()
+        .map(|c| c.frames.len())
+        .max()
+        .unwrap_or(0);
+    let band = 12 * CELL_HEIGHT;
+    let height = (chromagrams.len() * (band + SEPARATOR)).saturating_sub(SEPARATOR);
+    let mut image = Image::new(frames.max(1) * CELL_WIDTH, height.max(1));
+
+    for (i, chromagram) in chromagrams.iter().enumerate() {
+        let top = i * (band + SEPARATOR);
+        for (x, chroma) in chromagram.frames.iter().enumerate() {
+            for (pitch_class, &value) in chroma.iter().enumerate() {
+                let color = heat_color(value);
+                let row = top + (11 - pitch_class) * CELL_HEIGHT;
+                for y in row..row + CELL_HEIGHT {
+                    for px in x * CELL_WIDTH..(x + 1) * CELL_WIDTH {
+                        image.set(px, y, color);
+                    }
+                }
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_frame_centres() {
+        let chromagram = Chromagram {
+            sample_rate: 100.0,
+            fft_size: 10,
+            hop_size: 4,
+            frames: Vec::new(),
+            energy: Vec::new(),
+        };
+        assert_eq!(chromagram.time(0), 0.05);
+        assert_eq!(chromagram.time(3), 0.17);
+    }
+}
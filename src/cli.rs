@@ -0,0 +1,781 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::process::ExitCode;
+use std::str::FromStr;
+use wav_note_detector::batch;
+use wav_note_detector::decode::RawFormat;
+use wav_note_detector::midi::{MidiOptions, tempo_micros};
+use wav_note_detector::table::Delimiter;
+use wav_note_detector::{
+    A4, AnalyzerConfig, ChannelMode, ChromaConfig, KeyProfile, NoteNamer, Spelling,
+};
+
+// Process exit statuses, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Exit {
+    Ok = 0,
+    // Every input was read but nothing pitched was found
+    NoNotes = 1,
+    // The command line could not be understood
+    Usage = 2,
+    // An input is not a valid or supported WAV file
+    BadInput = 3,
+    // A file could not be read or written
+    Io = 4,
+}
+
+impl Exit {
+    // Sort a failure into bad input or I/O trouble
+    pub fn classify(error: &(dyn Error + 'static)) -> Exit {
+        match error.downcast_ref::<hound::Error>() {
+            Some(hound::Error::IoError(_)) => Exit::Io,
+            Some(_) => Exit::BadInput,
+            None if error.is::<io::Error>() => Exit::Io,
+            None => Exit::BadInput,
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    // Note histogram, onsets and note timeline
+    Analyze,
+    // Per-frame pitch track
+    Track,
+    // Note events and MIDI export
+    Transcribe,
+    // Offset of the recording's tuning from the reference pitch
+    Tune,
+    // Most likely musical key
+    Key,
+    // Pitch class energy per frame
+    Chroma,
+    // Chord timeline
+    Chords,
+    // File metadata without analysis
+    Info,
+    // Continuous readout of audio arriving on stdin
+    Live,
+    // Tuner display for audio arriving on stdin
+    Tuner,
+}
+
+impl Command {
+    const ALL: [Command; 10] = [
+        Command::Analyze,
+        Command::Track,
+        Command::Transcribe,
+        Command::Tune,
+        Command::Key,
+        Command::Chroma,
+        Command::Chords,
+        Command::Info,
+        Command::Live,
+        Command::Tuner,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Command::Analyze => "analyze",
+            Command::Track => "track",
+            Command::Transcribe => "transcribe",
+            Command::Tune => "tune",
+            Command::Key => "key",
+            Command::Chroma => "chroma",
+            Command::Chords => "chords",
+            Command::Info => "info",
+            Command::Live => "live",
+            Command::Tuner => "tuner",
+        }
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            Command::Analyze => "Most common notes, onsets and a note timeline (default)",
+            Command::Track => "Pitch of every analysis frame",
+            Command::Transcribe => "Note events with start, end and amplitude; MIDI export",
+            Command::Tune => "Tuning offset from the A4 reference with a cents histogram",
+            Command::Key => "Best matching major or minor key and the runner-ups",
+            Command::Chroma => "12-bin chroma of every frame; CSV, TSV, JSON or a PNG heat map",
+            Command::Chords => "Chord timeline from chord templates smoothed over time",
+            Command::Info => "Sample rate, channels, format and duration",
+            Command::Live => "Running note readout of WAV or raw PCM piped to stdin",
+            Command::Tuner => "Terminal tuner with a large note name and cents meter, fed by stdin",
+        }
+    }
+
+    fn formats(self) -> &'static [Format] {
+        match self {
+            Command::Track | Command::Chroma => {
+                &[Format::Text, Format::Json, Format::Csv, Format::Tsv]
+            }
+            Command::Tuner => &[Format::Text],
+            _ => &[Format::Text, Format::Json],
+        }
+    }
+
+    fn takes(self, group: Group) -> bool {
+        match group {
+            Group::Common => true,
+            Group::Analysis => self != Command::Info,
+            Group::Export => matches!(self, Command::Analyze | Command::Track),
+            Group::Midi => matches!(self, Command::Analyze | Command::Transcribe),
+            Group::Key => self == Command::Key,
+            Group::Chroma => matches!(self, Command::Chroma | Command::Chords),
+            Group::Naming => self != Command::Info,
+            Group::Live => matches!(self, Command::Live | Command::Tuner),
+            Group::Tuner => self == Command::Tuner,
+        }
+    }
+}
+
+// How results are printed to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err("expected text, json, csv or tsv".to_string()),
+        }
+    }
+}
+
+// Hop size as given on the command line, resolved once the sample rate is known
+#[derive(Debug, Clone, Copy)]
+pub enum Hop {
+    Samples(usize),
+    Millis(f32),
+}
+
+impl Hop {
+    pub fn samples(self, sample_rate: f32) -> usize {
+        match self {
+            Hop::Samples(n) => n,
+            Hop::Millis(ms) => ((ms / 1000.0 * sample_rate).round() as usize).max(1),
+        }
+    }
+}
+
+impl FromStr for Hop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hop = match s.strip_suffix("ms") {
+            Some(ms) => ms
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&ms: &f32| ms > 0.0)
+                .map(Hop::Millis),
+            None => s.parse().ok().filter(|&n| n > 0).map(Hop::Samples),
+        };
+        hop.ok_or("expected a positive number of samples or milliseconds (e.g. 10ms)".to_string())
+    }
+}
+
+// Frequency of A4 to analyze against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reference {
+    Fixed(f32),
+    // Estimated per file from the cents deviations of its notes
+    Auto,
+}
+
+impl FromStr for Reference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Reference::Auto);
+        }
+        s.strip_suffix("hz")
+            .or(s.strip_suffix("Hz"))
+            .unwrap_or(s)
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&hz: &f32| hz > 0.0 && hz.is_finite())
+            .map(Reference::Fixed)
+            .ok_or("expected a frequency in Hz (e.g. 415) or auto".to_string())
+    }
+}
+
+// How sharps and flats are chosen for note names
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeySpelling {
+    Fixed(Spelling),
+    // Spell in the key detected for each file
+    Detected,
+}
+
+impl FromStr for KeySpelling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "key" | "auto" => Ok(KeySpelling::Detected),
+            _ => s.parse().map(KeySpelling::Fixed),
+        }
+    }
+}
+
+// Everything parsed from the command line
+pub struct Options {
+    pub command: Command,
+    pub inputs: Vec<String>,
+    pub mode: ChannelMode,
+    pub config: AnalyzerConfig,
+    pub hop: Option<Hop>,
+    pub reference: Reference,
+    pub format: Format,
+    pub midi_path: Option<String>,
+    pub midi_options: MidiOptions,
+    pub tables: Vec<(String, Delimiter)>,
+    pub key_profile: KeyProfile,
+    pub chroma: ChromaConfig,
+    pub png_path: Option<String>,
+    pub spelling: KeySpelling,
+    // Octave convention and symbols; the spelling is resolved per file
+    pub namer: NoteNamer,
+    // Layout of stdin for live when it carries no WAV header
+    pub raw: RawFormat,
+    // Time constant of the tuner's pitch smoothing in seconds
+    pub smoothing: f32,
+    pub jobs: usize,
+}
+
+// What the command line asked for
+pub enum Invocation {
+    Run(Box<Options>),
+    Help(Option<Command>),
+    Version,
+}
+
+// Which commands accept a flag
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Group {
+    Common,
+    Analysis,
+    Export,
+    Midi,
+    Key,
+    Chroma,
+    Naming,
+    Live,
+    Tuner,
+}
+
+struct Flag {
+    long: &'static str,
+    short: Option<char>,
+    // Placeholder for the value in help, None for switches
+    value: Option<&'static str>,
+    help: &'static str,
+    group: Group,
+}
+
+const FLAGS: &[Flag] = &[
+    Flag {
+        long: "format",
+        short: Some('f'),
+        value: Some("FORMAT"),
+        help: "Output format: text or json (track also takes csv and tsv)",
+        group: Group::Common,
+    },
+    Flag {
+        long: "jobs",
+        short: Some('j'),
+        value: Some("N"),
+        help: "Files analyzed in parallel [default: number of cores]",
+        group: Group::Common,
+    },
+    Flag {
+        long: "threads",
+        short: Some('T'),
+        value: Some("N"),
+        help: "Threads analyzing the frames of each signal, for long files [default: 1]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "channel",
+        short: Some('c'),
+        value: Some("MODE"),
+        help: "mix, all, left, right or a channel index [default: mix]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "fft-size",
+        short: Some('n'),
+        value: Some("SAMPLES"),
+        help: "Samples per analysis frame [default: 4096]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "hop",
+        short: Some('H'),
+        value: Some("SAMPLES|Nms"),
+        help: "Distance between frames [default: half the FFT size]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "zero-padding",
+        short: Some('z'),
+        value: Some("FACTOR"),
+        help: "Pad each frame to FACTOR times its length [default: 1]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "window",
+        short: Some('w'),
+        value: Some("WINDOW"),
+        help: "hann, hamming, blackman, blackman-harris, kaiser[:beta], gaussian[:sigma]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "interpolation",
+        short: Some('i'),
+        value: Some("METHOD"),
+        help: "Peak refinement: none, parabolic or gaussian [default: parabolic]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "detector",
+        short: Some('d'),
+        value: Some("DETECTOR"),
+        help: "Pitch detector: peak, yin, mpm, hps or cepstrum [default: peak]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "polyphony",
+        short: Some('p'),
+        value: Some("N"),
+        help: "Maximum simultaneous notes per frame [default: 1]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "onset",
+        short: Some('o'),
+        value: Some("METHOD"),
+        help: "Onset detection: flux, hfc or complex [default: flux]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "a4",
+        short: Some('a'),
+        value: Some("HZ|auto"),
+        help: "Tuning reference, or auto to estimate it per file [default: 440]",
+        group: Group::Analysis,
+    },
+    Flag {
+        long: "csv",
+        short: None,
+        value: Some("PATH"),
+        help: "Also write the pitch track as CSV",
+        group: Group::Export,
+    },
+    Flag {
+        long: "tsv",
+        short: None,
+        value: Some("PATH"),
+        help: "Also write the pitch track as TSV",
+        group: Group::Export,
+    },
+    Flag {
+        long: "midi",
+        short: Some('m'),
+        value: Some("PATH"),
+        help: "Write the detected notes as a Standard MIDI File",
+        group: Group::Midi,
+    },
+    Flag {
+        long: "midi-format",
+        short: None,
+        value: Some("0|1"),
+        help: "MIDI file format [default: 1]",
+        group: Group::Midi,
+    },
+    Flag {
+        long: "tempo",
+        short: Some('t'),
+        value: Some("BPM"),
+        help: "MIDI tempo [default: 120]",
+        group: Group::Midi,
+    },
+    Flag {
+        long: "ppq",
+        short: None,
+        value: Some("TICKS"),
+        help: "MIDI ticks per quarter note [default: 480]",
+        group: Group::Midi,
+    },
+    Flag {
+        long: "profile",
+        short: None,
+        value: Some("PROFILE"),
+        help: "Key profiles: krumhansl or temperley [default: krumhansl]",
+        group: Group::Key,
+    },
+    Flag {
+        long: "min-octave",
+        short: None,
+        value: Some("OCTAVE"),
+        help: "Lowest octave folded into the chroma, C4 is middle C [default: 2]",
+        group: Group::Chroma,
+    },
+    Flag {
+        long: "max-octave",
+        short: None,
+        value: Some("OCTAVE"),
+        help: "Highest octave folded into the chroma [default: 7]",
+        group: Group::Chroma,
+    },
+    Flag {
+        long: "png",
+        short: None,
+        value: Some("PATH"),
+        help: "Also draw the chromagram as a PNG heat map",
+        group: Group::Chroma,
+    },
+    Flag {
+        long: "spelling",
+        short: Some('s'),
+        value: Some("SPELLING"),
+        help: "Note names with sharps, flats, in a given key (Eb, F#m) or key for the detected key [default: sharps]",
+        group: Group::Naming,
+    },
+    Flag {
+        long: "octaves",
+        short: None,
+        value: Some("CONVENTION"),
+        help: "Octave numbering: scientific (C4 is middle C), c3 or helmholtz [default: scientific]",
+        group: Group::Naming,
+    },
+    Flag {
+        long: "unicode",
+        short: Some('u'),
+        value: None,
+        help: "Write accidentals as \u{266f} and \u{266d}",
+        group: Group::Naming,
+    },
+    Flag {
+        long: "raw",
+        short: None,
+        value: Some("ENC[:RATE[:CH]]"),
+        help: "Layout of headerless input: u8, s16le, s24le, s32le or f32le [default: s16le:44100:1]",
+        group: Group::Live,
+    },
+    Flag {
+        long: "smoothing",
+        short: None,
+        value: Some("SECONDS"),
+        help: "Time constant of the pitch smoothing, 0 for none [default: 0.15]",
+        group: Group::Tuner,
+    },
+    Flag {
+        long: "help",
+        short: Some('h'),
+        value: None,
+        help: "Print help",
+        group: Group::Common,
+    },
+    Flag {
+        long: "version",
+        short: Some('V'),
+        value: None,
+        help: "Print version",
+        group: Group::Common,
+    },
+];
+
+fn parse_value<T>(flag: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid value '{}' for --{}: {}", value, flag, e))
+}
+
+// Parse the arguments after the program name
+pub fn parse(args: &[String]) -> Result<Invocation, String> {
+    let mut args = args.iter().peekable();
+    let mut named = true;
+    let command = match args.peek().map(|a| a.as_str()) {
+        Some("help") => {
+            args.next();
+            let topic = args.next().map(|name| find_command(name)).transpose()?;
+            return Ok(Invocation::Help(topic));
+        }
+        Some(name) => match Command::ALL.iter().find(|c| c.name() == name) {
+            Some(&command) => {
+                args.next();
+                command
+            }
+            // A bare file keeps working as `analyze`
+            None => {
+                named = false;
+                Command::Analyze
+            }
+        },
+        None => return Ok(Invocation::Help(None)),
+    };
+
+    let mut options = Options {
+        command,
+        inputs: Vec::new(),
+        mode: ChannelMode::Downmix,
+        config: AnalyzerConfig::default(),
+        hop: None,
+        reference: Reference::Fixed(A4),
+        format: Format::Text,
+        midi_path: None,
+        midi_options: MidiOptions::default(),
+        tables: Vec::new(),
+        key_profile: KeyProfile::default(),
+        chroma: ChromaConfig::default(),
+        png_path: None,
+        spelling: KeySpelling::Fixed(Spelling::Sharps),
+        namer: NoteNamer::default(),
+        raw: RawFormat::default(),
+        smoothing: 0.15,
+        jobs: batch::default_threads(),
+    };
+
+    let mut only_inputs = false;
+    while let Some(arg) = args.next() {
+        if only_inputs || arg == "-" || !arg.starts_with('-') {
+            options.inputs.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            only_inputs = true;
+            continue;
+        }
+
+        // --name value, --name=value, -x value or -xvalue
+        let (flag, inline) = if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let flag = FLAGS
+                .iter()
+                .find(|f| f.long == name)
+                .ok_or(format!("unknown option '--{}'", name))?;
+            (flag, inline)
+        } else {
+            let mut chars = arg[1..].chars();
+            let short = chars.next().unwrap_or('-');
+            let flag = FLAGS
+                .iter()
+                .find(|f| f.short == Some(short))
+                .ok_or(format!("unknown option '-{}'", short))?;
+            let rest: String = chars.collect();
+            (flag, (!rest.is_empty()).then_some(rest))
+        };
+
+        match flag.long {
+            "help" => return Ok(Invocation::Help(named.then_some(command))),
+            "version" => return Ok(Invocation::Version),
+            _ => {}
+        }
+        if !command.takes(flag.group) {
+            return Err(format!(
+                "--{} does not apply to the {} command",
+                flag.long,
+                command.name()
+            ));
+        }
+        if flag.value.is_none() {
+            if inline.is_some() {
+                return Err(format!("--{} takes no value", flag.long));
+            }
+            apply(&mut options, flag.long, "")?;
+            continue;
+        }
+        let value = match inline {
+            Some(value) => value,
+            None => args
+                .next()
+                .cloned()
+                .ok_or(format!("--{} needs a value", flag.long))?,
+        };
+        apply(&mut options, flag.long, &value)?;
+    }
+
+    if command.takes(Group::Live) {
+        // Only stdin, written as - or left out
+        let name = command.name();
+        if options.inputs.iter().any(|input| input != "-") {
+            return Err(format!(
+                "{} reads from stdin and takes no input files",
+                name
+            ));
+        }
+        if options.reference == Reference::Auto {
+            return Err(format!("{} needs a fixed reference, not --a4 auto", name));
+        }
+        if options.mode == ChannelMode::Each {
+            return Err(format!("{} follows one signal, not --channel all", name));
+        }
+        if options.spelling == KeySpelling::Detected {
+            return Err(format!(
+                "{} cannot detect the key, give one with --spelling",
+                name
+            ));
+        }
+        if !(options.smoothing >= 0.0 && options.smoothing.is_finite()) {
+            return Err("--smoothing must be a number of seconds, 0 or more".to_string());
+        }
+    } else if options.inputs.is_empty() {
+        return Err("no input given".to_string());
+    }
+    if !command.formats().contains(&options.format) {
+        return Err(format!(
+            "the {} command does not support --format {:?}",
+            command.name(),
+            options.format
+        ));
+    }
+    if options.config.fft_size < 2 || options.config.zero_padding == 0 {
+        return Err("--fft-size must be at least 2 and --zero-padding at least 1".to_string());
+    }
+    if command == Command::Tune && options.reference == Reference::Auto {
+        return Err("tune measures against a fixed reference, not --a4 auto".to_string());
+    }
+    if options.chroma.min_octave > options.chroma.max_octave {
+        return Err("--min-octave must not be above --max-octave".to_string());
+    }
+    let midi = &options.midi_options;
+    if tempo_micros(midi.tempo_bpm).is_none() {
+        return Err("--tempo must be a number of beats per minute of at least 3.58".to_string());
+    }
+    if midi.ppq == 0 || midi.ppq > 0x7fff {
+        return Err("--ppq must be within 1..=32767".to_string());
+    }
+    if options.jobs == 0 || options.config.threads == 0 {
+        return Err("--jobs and --threads must be at least 1".to_string());
+    }
+    Ok(Invocation::Run(Box::new(options)))
+}
+
+fn find_command(name: &str) -> Result<Command, String> {
+    Command::ALL
+        .iter()
+        .copied()
+        .find(|c| c.name() == name)
+        .ok_or(format!("unknown command '{}'", name))
+}
+
+fn apply(o: &mut Options, flag: &str, value: &str) -> Result<(), String> {
+    match flag {
+        "format" => o.format = parse_value(flag, value)?,
+        "jobs" => o.jobs = parse_value(flag, value)?,
+        "threads" => o.config.threads = parse_value(flag, value)?,
+        "channel" => o.mode = parse_value(flag, value)?,
+        "fft-size" => o.config.fft_size = parse_value(flag, value)?,
+        "hop" => o.hop = Some(parse_value(flag, value)?),
+        "zero-padding" => o.config.zero_padding = parse_value(flag, value)?,
+        "window" => o.config.window = parse_value(flag, value)?,
+        "interpolation" => o.config.interpolation = parse_value(flag, value)?,
+        "detector" => o.config.detector = parse_value(flag, value)?,
+        "polyphony" => o.config.polyphony = parse_value(flag, value)?,
+        "onset" => o.config.onset_method = parse_value(flag, value)?,
+        "a4" => o.reference = parse_value(flag, value)?,
+        "csv" => o.tables.push((value.to_string(), Delimiter::Comma)),
+        "tsv" => o.tables.push((value.to_string(), Delimiter::Tab)),
+        "midi" => o.midi_path = Some(value.to_string()),
+        "midi-format" => o.midi_options.format = parse_value(flag, value)?,
+        "tempo" => o.midi_options.tempo_bpm = parse_value(flag, value)?,
+        "ppq" => o.midi_options.ppq = parse_value(flag, value)?,
+        "profile" => o.key_profile = parse_value(flag, value)?,
+        "min-octave" => o.chroma.min_octave = parse_value(flag, value)?,
+        "max-octave" => o.chroma.max_octave = parse_value(flag, value)?,
+        "png" => o.png_path = Some(value.to_string()),
+        "spelling" => o.spelling = parse_value(flag, value)?,
+        "octaves" => o.namer.octaves = parse_value(flag, value)?,
+        "unicode" => o.namer.unicode = true,
+        "raw" => o.raw = parse_value(flag, value)?,
+        "smoothing" => o.smoothing = parse_value(flag, value)?,
+        _ => unreachable!("flag --{} is in FLAGS but not handled", flag),
+    }
+    Ok(())
+}
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  1  No notes detected
+  2  Invalid command line
+  3  Input is not a valid or supported WAV file
+  4  I/O error";
+
+// Help text for one command, or the overview
+pub fn help(program: &str, command: Option<Command>) -> String {
+    let mut out = String::new();
+    match command {
+        None => {
+            out.push_str(&format!(
+                "Detect musical notes in WAV files\n\n\
+                 Usage: {} [COMMAND] [OPTIONS] <INPUT>...\n\n\
+                 INPUT is a WAV file, a directory searched recursively, or a glob\n\
+                 (*, ?, [...], **).\n\nCommands:\n",
+                program
+            ));
+            for command in Command::ALL {
+                out.push_str(&format!("  {:<12}{}\n", command.name(), command.summary()));
+            }
+            out.push_str(&format!(
+                "  {:<12}Print help for a command\n\n\
+                 Run '{} help <COMMAND>' for the options of a command.\n\n",
+                "help", program
+            ));
+        }
+        Some(command) => {
+            let inputs = match command {
+                Command::Live | Command::Tuner => "[-]",
+                _ => "<INPUT>...",
+            };
+            out.push_str(&format!(
+                "{}\n\nUsage: {} {} [OPTIONS] {}\n\nOptions:\n",
+                command.summary(),
+                program,
+                command.name(),
+                inputs
+            ));
+            for flag in FLAGS.iter().filter(|f| command.takes(f.group)) {
+                let short = flag
+                    .short
+                    .map_or("    ".to_string(), |c| format!("-{}, ", c));
+                let long = match flag.value {
+                    Some(value) => format!("--{} <{}>", flag.long, value),
+                    None => format!("--{}", flag.long),
+                };
+                out.push_str(&format!("  {}{:<28}{}\n", short, long, flag.help));
+            }
+            out.push('\n');
+        }
+    }
+    out.push_str(EXIT_CODES);
+    out
+}
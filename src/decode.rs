@@ -0,0 +1,579 @@
+use crate::channels::{self, ChannelMode, Signal};
+use hound::{SampleFormat, WavReader, WavSpec};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Chain, Cursor, Read, Take};
+use std::path::Path;
+use std::str::FromStr;
+
+// Decoded WAV file: interleaved samples normalized to [-1, 1]
+pub struct Audio {
+    pub spec: WavSpec,
+    pub samples: Vec<f32>,
+}
+
+impl Audio {
+    pub fn sample_rate(&self) -> f32 {
+        self.spec.sample_rate as f32
+    }
+
+    pub fn channels(&self) -> usize {
+        self.spec.channels as usize
+    }
+
+    // Duration in seconds, counted in frames rather than interleaved samples
+    pub fn duration(&self) -> f32 {
+        (self.samples.len() / self.channels().max(1)) as f32 / self.sample_rate()
+    }
+
+    // Mono signals to analyze for the given channel mode
+    pub fn signals(&self, mode: ChannelMode) -> Result<Vec<Signal>, Box<dyn Error>> {
+        channels::select(&self.samples, self.channels(), mode)
+    }
+}
+
+// Open and decode a WAV file from disk
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Audio, Box<dyn Error>> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = read_samples(&mut reader)?;
+    Ok(Audio { spec, samples })
+}
+
+// Read every sample from the WAV file and normalize it to f32 in [-1, 1].
+// Samples stay interleaved; any read error aborts instead of being dropped.
+pub fn read_samples<R: Read>(reader: &mut WavReader<R>) -> Result<Vec<f32>, Box<dyn Error>> {
+    let mut samples = Vec::with_capacity(reader.len() as usize);
+    while read_block(reader, usize::MAX, &mut samples)? > 0 {}
+    Ok(samples)
+}
+
+// Reads a WAV file a block at a time, so long recordings can be analyzed
+// without decoding them into memory first
+pub struct SampleReader<R> {
+    reader: WavReader<R>,
+    // Frames (one sample per channel) per block
+    block_frames: usize,
+}
+
+impl SampleReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P, block_frames: usize) -> Result<Self, Box<dyn Error>> {
+        Ok(SampleReader::new(WavReader::open(path)?, block_frames))
+    }
+}
+
+impl<R: Read> SampleReader<R> {
+    pub fn new(reader: WavReader<R>, block_frames: usize) -> Self {
+        SampleReader {
+            reader,
+            block_frames: block_frames.max(1),
+        }
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.reader.spec()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.reader.spec().channels as usize
+    }
+
+    // Duration in seconds according to the header
+    pub fn duration(&self) -> f32 {
+        self.reader.duration() as f32 / self.reader.spec().sample_rate as f32
+    }
+
+    // Next block of interleaved samples normalized to [-1, 1], None at the end
+    pub fn next_block(&mut self) -> Result<Option<Vec<f32>>, Box<dyn Error>> {
+        let channels = self.reader.spec().channels.max(1) as usize;
+        let mut block = Vec::with_capacity(self.block_frames * channels);
+        let read = read_block(&mut self.reader, self.block_frames * channels, &mut block)?;
+        Ok((read > 0).then_some(block))
+    }
+}
+
+// Append up to `max` normalized samples to `out`, returning how many were read
+fn read_block<R: Read>(
+    reader: &mut WavReader<R>,
+    max: usize,
+    out: &mut Vec<f32>,
+) -> Result<usize, Box<dyn Error>> {
+    let spec = reader.spec();
+    let before = out.len();
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => {
+            for sample in reader.samples::<f32>().take(max) {
+                out.push(sample?);
+            }
+        }
+        (SampleFormat::Int, 8) => int_samples::<R, i8>(reader, 8, max, out)?,
+        (SampleFormat::Int, 16) => int_samples::<R, i16>(reader, 16, max, out)?,
+        (SampleFormat::Int, 24) | (SampleFormat::Int, 32) => {
+            int_samples::<R, i32>(reader, spec.bits_per_sample, max, out)?
+        }
+        (format, bits) => {
+            return Err(format!("unsupported sample format: {}-bit {:?}", bits, format).into());
+        }
+    }
+    Ok(out.len() - before)
+}
+
+// Integer PCM is scaled by 2^(bits - 1) so full scale maps to [-1, 1)
+fn int_samples<R, S>(
+    reader: &mut WavReader<R>,
+    bits: u16,
+    max: usize,
+    out: &mut Vec<f32>,
+) -> Result<(), Box<dyn Error>>
+where
+    R: Read,
+    S: hound::Sample + Into<i32>,
+{
+    let scale = 1.0 / (1u64 << (bits - 1)) as f64;
+    for sample in reader.samples::<S>().take(max) {
+        out.push((sample?.into() as f64 * scale) as f32);
+    }
+    Ok(())
+}
+
+// Sample encoding of headerless PCM, always little-endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    U8,
+    S16,
+    S24,
+    S32,
+    F32,
+}
+
+impl Encoding {
+    fn bytes(self) -> usize {
+        match self {
+            Encoding::U8 => 1,
+            Encoding::S16 => 2,
+            Encoding::S24 => 3,
+            Encoding::S32 | Encoding::F32 => 4,
+        }
+    }
+
+    // Normalize one sample of `self.bytes()` bytes to [-1, 1]
+    fn decode(self, b: &[u8]) -> f32 {
+        match self {
+            Encoding::U8 => (b[0] as f32 - 128.0) / 128.0,
+            Encoding::S16 => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+            // Shift into the top of an i32 to sign extend
+            Encoding::S24 => (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.0,
+            Encoding::S32 => {
+                (i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2147483648.0) as f32
+            }
+            Encoding::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u8" => Ok(Encoding::U8),
+            "s16" | "s16le" => Ok(Encoding::S16),
+            "s24" | "s24le" => Ok(Encoding::S24),
+            "s32" | "s32le" => Ok(Encoding::S32),
+            "f32" | "f32le" => Ok(Encoding::F32),
+            _ => Err(format!(
+                "invalid encoding '{}' (expected u8, s16le, s24le, s32le or f32le)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::U8 => "u8",
+            Encoding::S16 => "s16le",
+            Encoding::S24 => "s24le",
+            Encoding::S32 => "s32le",
+            Encoding::F32 => "f32le",
+        })
+    }
+}
+
+// Layout of headerless PCM, written ENCODING[:RATE[:CHANNELS]]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawFormat {
+    pub encoding: Encoding,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Default for RawFormat {
+    // What `arecord` records by default, apart from the rate
+    fn default() -> Self {
+        RawFormat {
+            encoding: Encoding::S16,
+            sample_rate: 44100,
+            channels: 1,
+        }
+    }
+}
+
+impl FromStr for RawFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let mut format = RawFormat {
+            encoding: parts.next().unwrap_or_default().parse()?,
+            ..RawFormat::default()
+        };
+        if let Some(rate) = parts.next() {
+            format.sample_rate = rate
+                .parse()
+                .ok()
+                .filter(|&r| r > 0)
+                .ok_or(format!("invalid sample rate '{}'", rate))?;
+        }
+        if let Some(channels) = parts.next() {
+            format.channels = channels
+                .parse()
+                .ok()
+                .filter(|&c| c > 0)
+                .ok_or(format!("invalid channel count '{}'", channels))?;
+        }
+        if parts.next().is_some() {
+            return Err("expected ENCODING[:RATE[:CHANNELS]]".to_string());
+        }
+        Ok(format)
+    }
+}
+
+impl fmt::Display for RawFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.encoding, self.sample_rate, self.channels
+        )
+    }
+}
+
+// Reads headerless PCM as it arrives, for pipes from recorders
+pub struct RawReader<R> {
+    reader: R,
+    format: RawFormat,
+    buffer: Vec<u8>,
+    // Bytes at the start of `buffer` left over from an incomplete frame
+    pending: usize,
+}
+
+impl<R: Read> RawReader<R> {
+    pub fn new(reader: R, format: RawFormat, block_frames: usize) -> Self {
+        let frame_bytes = format.encoding.bytes() * format.channels as usize;
+        RawReader {
+            reader,
+            format,
+            buffer: vec![0; block_frames.max(1) * frame_bytes],
+            pending: 0,
+        }
+    }
+
+    pub fn format(&self) -> RawFormat {
+        self.format
+    }
+
+    // Interleaved samples of the whole frames that have arrived, waiting
+    // only for the first bytes; None at the end of the input
+    pub fn next_block(&mut self) -> io::Result<Option<Vec<f32>>> {
+        let sample_bytes = self.format.encoding.bytes();
+        let frame_bytes = sample_bytes * self.format.channels as usize;
+        loop {
+            let read = match self.reader.read(&mut self.buffer[self.pending..]) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if read == 0 {
+                return Ok(None);
+            }
+            let available = self.pending + read;
+            let whole = available / frame_bytes * frame_bytes;
+            let samples: Vec<f32> = self.buffer[..whole]
+                .chunks_exact(sample_bytes)
+                .map(|b| self.format.encoding.decode(b))
+                .collect();
+            self.buffer.copy_within(whole..available, 0);
+            self.pending = available - whole;
+            if !samples.is_empty() {
+                return Ok(Some(samples));
+            }
+        }
+    }
+}
+
+// A reader with the bytes inspected by `open_input` put back in front,
+// limited to the data chunk of a WAV stream
+pub type Peeked<R> = Take<Chain<Cursor<Vec<u8>>, R>>;
+
+// Treat `reader` as WAV if it starts with a RIFF header, otherwise as raw
+// PCM laid out as `raw`. The WAV header is read here and the data chunk
+// streamed as raw PCM, up to its declared length so chunks after it aren't
+// played as samples. Recorders streaming WAV cannot know the length up
+// front and write a placeholder (0, 0x7FFFFFFF or 0xFFFFFFFF); those data
+// chunks run to the end of the input.
+pub fn open_input<R: Read>(
+    mut reader: R,
+    raw: RawFormat,
+    block_frames: usize,
+) -> Result<RawReader<Peeked<R>>, Box<dyn Error>> {
+    let mut magic = Vec::with_capacity(4);
+    (&mut reader).take(4).read_to_end(&mut magic)?;
+    if magic == b"RIFF" {
+        let (format, data_len) = read_wav_header(&mut reader)?;
+        let limit = match data_len {
+            0 | 0x7FFF_FFFF | 0xFFFF_FFFF => u64::MAX,
+            len => len as u64,
+        };
+        return Ok(RawReader::new(
+            Cursor::new(Vec::new()).chain(reader).take(limit),
+            format,
+            block_frames,
+        ));
+    }
+    Ok(RawReader::new(
+        Cursor::new(magic).chain(reader).take(u64::MAX),
+        raw,
+        block_frames,
+    ))
+}
+
+// Sample layout and declared data length from the chunks of a WAV stream,
+// past the RIFF tag and up to the start of the data chunk's samples
+fn read_wav_header<R: Read>(reader: &mut R) -> Result<(RawFormat, u32), Box<dyn Error>> {
+    let truncated = |e: io::Error| -> Box<dyn Error> {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            "truncated WAV header".into()
+        } else {
+            e.into()
+        }
+    };
+    let mut riff = [0; 8];
+    reader.read_exact(&mut riff).map_err(truncated)?;
+    if &riff[4..] != b"WAVE" {
+        return Err("not a WAVE file".into());
+    }
+    let mut format = None;
+    loop {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).map_err(truncated)?;
+        let declared = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let size = declared as u64;
+        match &header[..4] {
+            b"data" => {
+                let format = format.ok_or("WAV data chunk before fmt chunk")?;
+                return Ok((format, declared));
+            }
+            b"fmt " if size >= 16 => {
+                let mut fmt = vec![0; size as usize];
+                reader.read_exact(&mut fmt).map_err(truncated)?;
+                if size % 2 == 1 {
+                    reader.read_exact(&mut [0]).map_err(truncated)?;
+                }
+                format = Some(wav_format(&fmt)?);
+            }
+            // Other chunks (LIST, fact, ...) are skipped, padded to even sizes
+            _ => {
+                let skip = size + size % 2;
+                let skipped = io::copy(&mut reader.take(skip), &mut io::sink())?;
+                if skipped < skip {
+                    return Err("truncated WAV header".into());
+                }
+            }
+        }
+    }
+}
+
+// Raw layout described by the body of a fmt chunk
+fn wav_format(fmt: &[u8]) -> Result<RawFormat, Box<dyn Error>> {
+    let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+    let mut tag = u16_at(0);
+    let channels = u16_at(2);
+    let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+    let bits = u16_at(14);
+    // WAVE_FORMAT_EXTENSIBLE keeps the real format in its sub-format GUID
+    if tag == 0xFFFE && fmt.len() >= 26 {
+        tag = u16_at(24);
+    }
+    let encoding = match (tag, bits) {
+        (1, 8) => Encoding::U8,
+        (1, 16) => Encoding::S16,
+        (1, 24) => Encoding::S24,
+        (1, 32) => Encoding::S32,
+        (3, 32) => Encoding::F32,
+        _ => {
+            return Err(format!(
+                "unsupported WAV encoding (format {}, {} bits per sample)",
+                tag, bits
+            )
+            .into());
+        }
+    };
+    if channels == 0 || sample_rate == 0 {
+        return Err("WAV header has no channels or a zero sample rate".into());
+    }
+    Ok(RawFormat {
+        encoding,
+        sample_rate,
+        channels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavWriter;
+
+    // In-memory WAV file of stereo frames, each sample given as a fraction
+    // of full scale
+    fn wav(bits: u16, format: SampleFormat, samples: &[f64]) -> Vec<u8> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: bits,
+            sample_format: format,
+        };
+        let mut out = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut out, spec).unwrap();
+        let full = (1i64 << (bits - 1)) as f64;
+        for &sample in samples {
+            match (format, bits) {
+                (SampleFormat::Float, _) => writer.write_sample(sample as f32),
+                (_, 8) => writer.write_sample((sample * full) as i8),
+                (_, 16) => writer.write_sample((sample * full) as i16),
+                _ => writer.write_sample((sample * full).min(full - 1.0) as i32),
+            }
+            .unwrap();
+        }
+        writer.finalize().unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn every_sample_format() {
+        let samples = [0.0, 0.5, -0.5, -1.0, 0.25, -0.25];
+        for (bits, format) in [
+            (8, SampleFormat::Int),
+            (16, SampleFormat::Int),
+            (24, SampleFormat::Int),
+            (32, SampleFormat::Int),
+            (32, SampleFormat::Float),
+        ] {
+            let data = wav(bits, format, &samples);
+            let mut reader = WavReader::new(data.as_slice()).unwrap();
+            let decoded = read_samples(&mut reader).unwrap();
+            assert_eq!(
+                decoded,
+                samples.map(|s| s as f32),
+                "{}-bit {:?}",
+                bits,
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn blocks_hold_whole_frames() {
+        let samples = [0.0, 0.5, -0.5, -1.0, 0.25, -0.25];
+        let data = wav(16, SampleFormat::Int, &samples);
+        let mut reader = SampleReader::new(WavReader::new(data.as_slice()).unwrap(), 2);
+        assert_eq!(reader.channels(), 2);
+        assert_eq!(reader.duration(), 3.0 / 8000.0);
+        assert_eq!(
+            reader.next_block().unwrap().unwrap(),
+            [0.0, 0.5, -0.5, -1.0]
+        );
+        assert_eq!(reader.next_block().unwrap().unwrap(), [0.25, -0.25]);
+        assert!(reader.next_block().unwrap().is_none());
+    }
+
+    // WAV header for 16-bit mono at 8 kHz with a LIST chunk before the data
+    // and the given declared data length
+    fn streamed_wav(data_len: u32, samples: &[i16]) -> Vec<u8> {
+        let mut wav = b"RIFF".to_vec();
+        wav.extend(u32::MAX.to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(8000u32.to_le_bytes());
+        wav.extend(16000u32.to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"LIST");
+        wav.extend(3u32.to_le_bytes());
+        wav.extend(b"abc\0");
+        wav.extend(b"data");
+        wav.extend(data_len.to_le_bytes());
+        for sample in samples {
+            wav.extend(sample.to_le_bytes());
+        }
+        wav
+    }
+
+    fn read_all<R: Read>(mut reader: RawReader<R>) -> Vec<f32> {
+        let mut samples = Vec::new();
+        while let Some(block) = reader.next_block().unwrap() {
+            samples.extend(block);
+        }
+        samples
+    }
+
+    #[test]
+    fn wav_stream_ignores_placeholder_length() {
+        let samples = [0, 16384, -16384, 32767, -32768];
+        for data_len in [0, 0x7FFF_FFFF, u32::MAX] {
+            let wav = streamed_wav(data_len, &samples);
+            let input = open_input(wav.as_slice(), RawFormat::default(), 2).unwrap();
+            assert_eq!(input.format().to_string(), "s16le:8000:1");
+            assert_eq!(
+                read_all(input),
+                [0.0, 0.5, -0.5, 32767.0 / 32768.0, -1.0],
+                "data length {:#x}",
+                data_len
+            );
+        }
+    }
+
+    #[test]
+    fn wav_stream_stops_at_data_length() {
+        let samples = [0, 16384, -16384];
+        let mut wav = streamed_wav(6, &samples);
+        wav.extend(b"LIST");
+        wav.extend(4u32.to_le_bytes());
+        wav.extend(b"abcd");
+        let input = open_input(wav.as_slice(), RawFormat::default(), 2).unwrap();
+        assert_eq!(read_all(input), [0.0, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn raw_stream_keeps_first_bytes() {
+        let pcm = [1u8, 0, 0, 128, 255];
+        let format = "s16le:22050:2".parse().unwrap();
+        let input = open_input(&pcm[..], format, 16).unwrap();
+        assert_eq!(input.format(), format);
+        // The trailing odd byte is an incomplete frame and dropped
+        assert_eq!(read_all(input), [1.0 / 32768.0, -1.0]);
+    }
+
+    #[test]
+    fn unsupported_wav_stream() {
+        let mut wav = streamed_wav(0, &[]);
+        // 12-bit PCM
+        wav[34] = 12;
+        assert!(open_input(wav.as_slice(), RawFormat::default(), 16).is_err());
+        assert!(open_input(&b"RIFF\0\0"[..], RawFormat::default(), 16).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Forward FFT of real input. An even length N runs as a complex FFT of N/2
+// points over the even samples in the real parts and the odd samples in the
+// imaginary parts, then twiddles the result apart into the N/2 + 1 bins of
+// the real spectrum; that is about half the work of a complex FFT of N
+// points. Odd lengths fall back to the full complex FFT.
+pub struct RealFft {
+    len: usize,
+    fft: Arc<dyn Fft<f32>>,
+    // exp(-2 pi i k / N) for k in 0..=N/4, empty for odd lengths
+    twiddles: Vec<Complex<f32>>,
+}
+
+impl RealFft {
+    pub fn new(len: usize, planner: &mut FftPlanner<f32>) -> Self {
+        if len % 2 == 1 {
+            return RealFft {
+                len,
+                fft: planner.plan_fft_forward(len),
+                twiddles: Vec::new(),
+            };
+        }
+        let half = len / 2;
+        let twiddles = (0..=half / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f64 / len as f64;
+                Complex::new(angle.cos() as f32, angle.sin() as f32)
+            })
+            .collect();
+        RealFft {
+            len,
+            fft: planner.plan_fft_forward(half),
+            twiddles,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Zeroed buffer to transform into, of which the first N/2 + 1 bins are
+    // the spectrum
+    pub fn make_output(&self) -> Vec<Complex<f32>> {
+        let len = if self.twiddles.is_empty() {
+            self.len
+        } else {
+            self.len / 2 + 1
+        };
+        vec![Complex::new(0.0, 0.0); len]
+    }
+
+    // Scratch space for `process`, kept by the caller between frames
+    pub fn make_scratch(&self) -> Vec<Complex<f32>> {
+        vec![Complex::new(0.0, 0.0); self.fft.get_inplace_scratch_len()]
+    }
+
+    // Spectrum of `input` zero padded to the transform length, bins 0 to
+    // N/2 in `output[..N/2 + 1]`
+    pub fn process(
+        &self,
+        input: &[f32],
+        output: &mut [Complex<f32>],
+        scratch: &mut [Complex<f32>],
+    ) {
+        let input = &input[..input.len().min(self.len)];
+        if self.twiddles.is_empty() {
+            output.fill(Complex::new(0.0, 0.0));
+            for (bin, &sample) in output.iter_mut().zip(input) {
+                bin.re = sample;
+            }
+            self.fft.process_with_scratch(output, scratch);
+            return;
+        }
+
+        let half = self.len / 2;
+        let pairs = input.chunks_exact(2);
+        let last = pairs.remainder().first().copied();
+        let filled = pairs.len();
+        for (bin, pair) in output.iter_mut().zip(pairs) {
+            *bin = Complex::new(pair[0], pair[1]);
+        }
+        output[filled..half].fill(Complex::new(0.0, 0.0));
+        if let Some(last) = last {
+            output[filled].re = last;
+        }
+        self.fft.process_with_scratch(&mut output[..half], scratch);
+
+        // Z is the packed FFT. With E and O the spectra of the even and odd
+        // samples, E[k] = (Z[k] + conj Z[h-k]) / 2, O[k] = -i (Z[k] - conj
+        // Z[h-k]) / 2 and X[k] = E[k] + W^k O[k], X[h-k] = conj(E[k] - W^k
+        // O[k]), so each pair of bins is rebuilt in place.
+        let z0 = output[0];
+        output[0] = Complex::new(z0.re + z0.im, 0.0);
+        output[half] = Complex::new(z0.re - z0.im, 0.0);
+        for k in 1..=half / 2 {
+            let a = output[k];
+            let b = output[half - k].conj();
+            let even = (a + b) * 0.5;
+            let odd = Complex::new(0.0, -0.5) * (a - b);
+            let twiddled = self.twiddles[k] * odd;
+            output[half - k] = (even - twiddled).conj();
+            output[k] = even + twiddled;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compare against a complex FFT of the zero padded input
+    fn check(len: usize, input_len: usize) {
+        let input: Vec<f32> = (0..input_len)
+            .map(|i| ((i * 7919) % 101) as f32 / 50.0 - 1.0)
+            .collect();
+        let mut planner = FftPlanner::new();
+        let mut expected: Vec<Complex<f32>> = (0..len)
+            .map(|i| Complex::new(input.get(i).copied().unwrap_or(0.0), 0.0))
+            .collect();
+        planner.plan_fft_forward(len).process(&mut expected);
+
+        let fft = RealFft::new(len, &mut planner);
+        let mut output = fft.make_output();
+        let mut scratch = fft.make_scratch();
+        // Leftovers from an earlier frame must not leak into the next
+        output.fill(Complex::new(9.0, 9.0));
+        fft.process(&input, &mut output, &mut scratch);
+        let scale = expected.iter().map(|c| c.norm()).fold(0.0, f32::max);
+        for (bin, want) in expected[..=len / 2].iter().enumerate() {
+            let error = (output[bin] - want).norm() / scale;
+            assert!(
+                error < 1e-5,
+                "len {} bin {}: {} vs {}",
+                len,
+                bin,
+                output[bin],
+                want
+            );
+        }
+    }
+
+    #[test]
+    fn matches_complex_fft() {
+        for (len, input_len) in [(2, 2), (8, 8), (1024, 1024), (1000, 1000), (15, 15)] {
+            check(len, input_len);
+        }
+    }
+
+    #[test]
+    fn zero_pads_short_input() {
+        check(4096, 1024);
+        check(64, 33);
+        check(21, 10);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+use std::str::FromStr;
+
+// How to refine the location of a spectral peak between FFT bins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    // Report the bin center as is
+    None,
+    // Fit a parabola through the peak bin and its neighbours
+    #[default]
+    Parabolic,
+    // Fit a parabola to log magnitudes, exact for a Gaussian shaped peak
+    Gaussian,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Interpolation::None),
+            "parabolic" | "quadratic" => Ok(Interpolation::Parabolic),
+            "gaussian" => Ok(Interpolation::Gaussian),
+            _ => Err(format!(
+                "invalid interpolation '{}' (expected none, parabolic or gaussian)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Interpolation::None => "none",
+            Interpolation::Parabolic => "parabolic",
+            Interpolation::Gaussian => "gaussian",
+        })
+    }
+}
+
+// Refine the peak at `bin` of a magnitude spectrum.
+// Returns the fractional bin position and the interpolated peak magnitude.
+pub fn refine_peak(mags: &[f32], bin: usize, method: Interpolation) -> (f32, f32) {
+    let peak = mags[bin];
+    if bin == 0 || bin + 1 >= mags.len() {
+        return (bin as f32, peak);
+    }
+    let (left, right) = (mags[bin - 1], mags[bin + 1]);
+    if left > peak || right > peak {
+        // Not a local maximum, nothing to refine
+        return (bin as f32, peak);
+    }
+    match method {
+        Interpolation::None => (bin as f32, peak),
+        Interpolation::Parabolic => refine_extremum(mags, bin),
+        Interpolation::Gaussian => {
+            // Logarithms of zero would blow up, fall back to the bin center
+            if left <= 0.0 || peak <= 0.0 || right <= 0.0 {
+                return (bin as f32, peak);
+            }
+            let (offset, height) = parabola_vertex(left.ln(), peak.ln(), right.ln());
+            (bin as f32 + offset, height.exp())
+        }
+    }
+}
+
+// Fractional position and value of the minimum or maximum at `i`, from the
+// parabola through it and its neighbours; edges are returned as they are
+pub fn refine_extremum(values: &[f32], i: usize) -> (f32, f32) {
+    if i == 0 || i + 1 >= values.len() {
+        return (i as f32, values[i]);
+    }
+    let (offset, value) = parabola_vertex(values[i - 1], values[i], values[i + 1]);
+    (i as f32 + offset, value)
+}
+
+// Vertex of the parabola through (-1, a), (0, b), (1, c). With b the
+// largest or smallest of the three it lies within half a step of 0.
+fn parabola_vertex(a: f32, b: f32, c: f32) -> (f32, f32) {
+    let denom = a - 2.0 * b + c;
+    if denom == 0.0 {
+        return (0.0, b);
+    }
+    let offset = (0.5 * (a - c) / denom).clamp(-0.5, 0.5);
+    (offset, b - 0.25 * (a - c) * offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::WindowKind;
+    use std::f32::consts::TAU;
+
+    const SIZE: usize = 256;
+
+    // Magnitude spectrum of a Hann windowed sinusoid at fractional `bin`
+    fn spectrum(bin: f32) -> Vec<f32> {
+        let window = WindowKind::Hann.generate(SIZE);
+        let samples: Vec<f32> = (0..SIZE)
+            .map(|i| (TAU * bin * i as f32 / SIZE as f32).sin() * window[i])
+            .collect();
+        (0..SIZE / 2)
+            .map(|k| {
+                let (re, im) = samples
+                    .iter()
+                    .enumerate()
+                    .fold((0.0, 0.0), |(re, im), (i, x)| {
+                        let phase = TAU * (k * i) as f32 / SIZE as f32;
+                        (re + x * phase.cos(), im - x * phase.sin())
+                    });
+                f32::hypot(re, im)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn off_bin_sinusoid() {
+        // A unit sinusoid under a Hann window peaks at SIZE / 4
+        let (bin, height) = (20.3, SIZE as f32 / 4.0);
+        let mags = spectrum(bin);
+        assert_eq!(
+            refine_peak(&mags, 20, Interpolation::None),
+            (20.0, mags[20])
+        );
+        let (parabolic, parabolic_peak) = refine_peak(&mags, 20, Interpolation::Parabolic);
+        assert!((parabolic - bin).abs() < 0.06, "{}", parabolic);
+        assert!(parabolic_peak > mags[20] && (parabolic_peak / height - 1.0).abs() < 0.05);
+        // The log parabola fits the Hann main lobe much more closely
+        let (gaussian, gaussian_peak) = refine_peak(&mags, 20, Interpolation::Gaussian);
+        assert!((gaussian - bin).abs() < 0.02, "{}", gaussian);
+        assert!(gaussian_peak > mags[20] && (gaussian_peak / height - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn fallbacks() {
+        let mags = [3.0, 1.0, 0.0, 2.0, 0.0, 0.5, 4.0];
+        for method in [Interpolation::Parabolic, Interpolation::Gaussian] {
+            // Edge bins have only one neighbour
+            assert_eq!(refine_peak(&mags, 0, method), (0.0, 3.0));
+            assert_eq!(refine_peak(&mags, 6, method), (6.0, 4.0));
+            // Neither is a bin that isn't a local maximum
+            assert_eq!(refine_peak(&mags, 1, method), (1.0, 1.0));
+        }
+        // Zero neighbours have no logarithm
+        assert_eq!(refine_peak(&mags, 3, Interpolation::Gaussian), (3.0, 2.0));
+        assert_eq!(refine_peak(&mags, 3, Interpolation::Parabolic), (3.0, 2.0));
+        // A flat top has no vertex to move to
+        assert_eq!(refine_extremum(&[1.0, 1.0, 1.0], 1), (1.0, 1.0));
+    }
+}
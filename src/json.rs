@@ -0,0 +1,161 @@
+use std::fmt::{self, Write};
+
+// Minimal JSON value, enough to emit reports without extra dependencies
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // Keys keep their insertion order so output is stable
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // Start an empty object to be filled with `field`
+    pub fn object() -> Self {
+        Json::Object(Vec::new())
+    }
+
+    // Add a field to an object, builder style
+    pub fn field(mut self, key: &str, value: impl Into<Json>) -> Self {
+        if let Json::Object(fields) = &mut self {
+            fields.push((key.to_string(), value.into()));
+        }
+        self
+    }
+
+    // Indented output, two spaces per level
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(0))
+            .expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>) -> fmt::Result {
+        let (open, close) = match self {
+            Json::Array(_) => ('[', ']'),
+            Json::Object(_) => ('{', '}'),
+            Json::Null => return out.write_str("null"),
+            Json::Bool(b) => return write!(out, "{}", b),
+            Json::Number(n) if n.is_finite() => return write!(out, "{}", n),
+            Json::Number(_) => return out.write_str("null"),
+            Json::String(s) => return write_string(out, s),
+        };
+        let items: Vec<(Option<&str>, &Json)> = match self {
+            Json::Array(items) => items.iter().map(|v| (None, v)).collect(),
+            Json::Object(fields) => fields.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+            _ => unreachable!(),
+        };
+        out.write_char(open)?;
+        if items.is_empty() {
+            return out.write_char(close);
+        }
+        let inner = indent.map(|i| i + 1);
+        for (i, (key, value)) in items.into_iter().enumerate() {
+            if i > 0 {
+                out.write_char(',')?;
+            }
+            newline(out, inner)?;
+            if let Some(key) = key {
+                write_string(out, key)?;
+                out.write_str(if indent.is_some() { ": " } else { ":" })?;
+            }
+            value.write(out, inner)?;
+        }
+        newline(out, indent)?;
+        out.write_char(close)
+    }
+}
+
+// Compact, single line output
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, None)?;
+        f.write_str(&out)
+    }
+}
+
+fn newline(out: &mut String, indent: Option<usize>) -> fmt::Result {
+    if let Some(level) = indent {
+        out.write_char('\n')?;
+        for _ in 0..level {
+            out.write_str("  ")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut String, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+// Go through the shortest decimal form so 0.1f32 prints as 0.1, not 0.10000000149011612
+impl From<f32> for Json {
+    fn from(n: f32) -> Self {
+        Json::Number(n.to_string().parse().unwrap_or(f64::NAN))
+    }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Self {
+        Json::Number(n)
+    }
+}
+
+macro_rules! json_from_int {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Json {
+            fn from(n: $t) -> Self {
+                Json::Number(n as f64)
+            }
+        })*
+    };
+}
+
+json_from_int!(u8, u16, u32, u64, usize, i32, i64);
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Self {
+        Json::Array(items.into_iter().map(Into::into).collect())
+    }
+}
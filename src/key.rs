@@ -0,0 +1,244 @@
+use crate::analysis::Analysis;
+use crate::naming::tonic_name;
+use std::fmt;
+use std::str::FromStr;
+
+// Krumhansl-Kessler probe tone ratings, tonic first
+const KRUMHANSL_MAJOR: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const KRUMHANSL_MINOR: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+// Temperley's profiles from the Kostka-Payne corpus, tonic first
+const TEMPERLEY_MAJOR: [f32; 12] = [
+    0.748, 0.060, 0.488, 0.082, 0.670, 0.460, 0.096, 0.715, 0.104, 0.366, 0.057, 0.400,
+];
+const TEMPERLEY_MINOR: [f32; 12] = [
+    0.712, 0.084, 0.474, 0.618, 0.049, 0.460, 0.105, 0.747, 0.404, 0.067, 0.133, 0.330,
+];
+
+// Key profiles the pitch class distribution is correlated against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyProfile {
+    #[default]
+    Krumhansl,
+    Temperley,
+}
+
+impl KeyProfile {
+    fn weights(self, mode: Mode) -> &'static [f32; 12] {
+        match (self, mode) {
+            (KeyProfile::Krumhansl, Mode::Major) => &KRUMHANSL_MAJOR,
+            (KeyProfile::Krumhansl, Mode::Minor) => &KRUMHANSL_MINOR,
+            (KeyProfile::Temperley, Mode::Major) => &TEMPERLEY_MAJOR,
+            (KeyProfile::Temperley, Mode::Minor) => &TEMPERLEY_MINOR,
+        }
+    }
+}
+
+impl FromStr for KeyProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "krumhansl" | "ks" => Ok(KeyProfile::Krumhansl),
+            "temperley" => Ok(KeyProfile::Temperley),
+            _ => Err(format!(
+                "invalid key profile '{}' (expected krumhansl or temperley)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for KeyProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            KeyProfile::Krumhansl => "krumhansl",
+            KeyProfile::Temperley => "temperley",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    // Pitch class of the tonic, 0 is C
+    pub tonic: u8,
+    pub mode: Mode,
+    // Letter index (0 is C) and accidental of the tonic as written, so Gb
+    // stays Gb rather than F#; None for detected keys, which take the
+    // conventional spelling
+    pub spelling: Option<(usize, i8)>,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", tonic_name(*self), self.mode)
+    }
+}
+
+// A tonic with any accidentals and an optional mode: C, Eb, F#m, Bb:min, c#minor
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid key '{}' (expected e.g. C, Eb, F#m or Bb:minor)", s);
+        let mut chars = s.chars();
+        let (letter, natural) = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => (0, 0),
+            Some('D') => (1, 2),
+            Some('E') => (2, 4),
+            Some('F') => (3, 5),
+            Some('G') => (4, 7),
+            Some('A') => (5, 9),
+            Some('B') => (6, 11),
+            _ => return Err(invalid()),
+        };
+        let mut rest = chars.as_str();
+        let mut tonic = natural;
+        let mut accidental = 0i8;
+        loop {
+            if let Some(r) = rest.strip_prefix(['#', '\u{266f}']) {
+                tonic += 1;
+                accidental += 1;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix(['b', '\u{266d}']) {
+                tonic += 11;
+                accidental -= 1;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let mode = match rest.trim_start_matches([':', ' ']) {
+            "" | "maj" | "major" | "M" => Mode::Major,
+            "m" | "min" | "minor" => Mode::Minor,
+            _ => return Err(invalid()),
+        };
+        Ok(Key {
+            tonic: (tonic % 12) as u8,
+            mode,
+            spelling: Some((letter, accidental)),
+        })
+    }
+}
+
+// How well one key fits a pitch class distribution
+#[derive(Debug, Clone, Copy)]
+pub struct KeyScore {
+    pub key: Key,
+    // Pearson correlation with the key's profile, -1 to 1
+    pub correlation: f32,
+}
+
+// Frames each pitch class sounds in, summed over every analysis, C first
+pub fn pitch_class_profile(analyses: &[&Analysis]) -> [f32; 12] {
+    let mut profile = [0.0; 12];
+    for analysis in analyses {
+        for &(note, count) in &analysis.note_counts {
+            profile[(note % 12) as usize] += count as f32;
+        }
+    }
+    profile
+}
+
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let mean_a = a.iter().sum::<f32>() / 12.0;
+    let mean_b = b.iter().sum::<f32>() / 12.0;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+    }
+    let norm = (var_a * var_b).sqrt();
+    if norm > 0.0 { cov / norm } else { 0.0 }
+}
+
+// Correlate a pitch class distribution with the profile of all 24 major and
+// minor keys (Krumhansl-Schmuckler). Best fit first; empty for a flat or
+// silent distribution.
+pub fn detect_key(profile: &[f32; 12], weights: KeyProfile) -> Vec<KeyScore> {
+    let min = profile.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = profile.iter().copied().fold(0.0, f32::max);
+    if max <= min {
+        return Vec::new();
+    }
+
+    let mut scores = Vec::with_capacity(24);
+    for mode in [Mode::Major, Mode::Minor] {
+        let template = weights.weights(mode);
+        for tonic in 0..12 {
+            // Rotate the distribution so the candidate tonic comes first
+            let mut rotated = [0.0; 12];
+            for (i, value) in rotated.iter_mut().enumerate() {
+                *value = profile[(i + tonic) % 12];
+            }
+            scores.push(KeyScore {
+                key: Key {
+                    tonic: tonic as u8,
+                    mode,
+                    spelling: None,
+                },
+                correlation: correlation(&rotated, template),
+            });
+        }
+    }
+    scores.sort_by(|a, b| b.correlation.total_cmp(&a.correlation));
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        let cases = [
+            ("C", 0, Mode::Major, (0, 0)),
+            ("c", 0, Mode::Major, (0, 0)),
+            ("Eb", 3, Mode::Major, (2, -1)),
+            ("E\u{266d}", 3, Mode::Major, (2, -1)),
+            ("F#m", 6, Mode::Minor, (3, 1)),
+            ("Bb:min", 10, Mode::Minor, (6, -1)),
+            ("c#minor", 1, Mode::Minor, (0, 1)),
+            ("A minor", 9, Mode::Minor, (5, 0)),
+            ("GM", 7, Mode::Major, (4, 0)),
+            ("Cb", 11, Mode::Major, (0, -1)),
+            ("B#", 0, Mode::Major, (6, 1)),
+            ("Fbb", 3, Mode::Major, (3, -2)),
+        ];
+        for (text, tonic, mode, spelling) in cases {
+            let key: Key = text.parse().unwrap();
+            assert_eq!(
+                key,
+                Key {
+                    tonic,
+                    mode,
+                    spelling: Some(spelling)
+                },
+                "{}",
+                text
+            );
+        }
+        for text in ["", "H", "C#x", "m", "Cminorr", "#C"] {
+            assert!(text.parse::<Key>().is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+pub mod analysis;
+pub mod batch;
+pub mod channels;
+pub mod chords;
+pub mod chroma;
+pub mod decode;
+pub mod fft;
+pub mod interpolate;
+pub mod json;
+pub mod key;
+pub mod midi;
+pub mod naming;
+pub mod notes;
+pub mod onset;
+pub mod pitch;
+pub mod png;
+pub mod report;
+pub mod segment;
+pub mod table;
+pub mod tuner;
+pub mod tuning;
+pub mod window;
+
+pub use analysis::{Analysis, Analyzer, AnalyzerConfig, Frame, Voice};
+pub use channels::{ChannelMode, Signal};
+pub use chords::{Chord, ChordSegment, Quality};
+pub use chroma::{ChromaConfig, Chromagram};
+pub use decode::Audio;
+pub use interpolate::Interpolation;
+pub use json::Json;
+pub use key::{Key, KeyProfile, KeyScore, Mode};
+pub use midi::{MidiFormat, MidiOptions, MidiTrack};
+pub use naming::{NoteNamer, Octaves, Spelling};
+pub use notes::{
+    A4, cents_from_note, freq_to_midi, freq_to_midi_note, midi_note_to_name, midi_to_freq,
+    pitch_class_name, shift_reference,
+};
+pub use onset::{Onset, OnsetMethod};
+pub use pitch::{DetectorKind, Pitch, PitchDetector};
+pub use segment::{NoteEvent, segment};
+pub use table::Delimiter;
+pub use window::WindowKind;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    // The pipeline runs through the public API alone, from samples to
+    // named note events
+    #[test]
+    fn samples_to_notes() {
+        let sample_rate = 44100.0;
+        let samples: Vec<f32> = (0..44100)
+            .map(|i| 0.5 * (TAU * 440.0 * i as f32 / sample_rate).sin())
+            .collect();
+        let analysis = Analyzer::new(AnalyzerConfig::default()).analyze(&samples, sample_rate);
+        assert_eq!(
+            analysis.note_counts.first().map(|&(note, _)| note),
+            Some(69)
+        );
+        let events = segment(&analysis);
+        assert_eq!(events.len(), 1);
+        assert_eq!(NoteNamer::default().name(events[0].note), "A4");
+    }
+}
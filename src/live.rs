@@ -0,0 +1,163 @@
+use crate::cli::{Exit, Format, KeySpelling, Options, Reference};
+use std::error::Error;
+use std::io::{self, IsTerminal, Write};
+use wav_note_detector::channels;
+use wav_note_detector::decode;
+use wav_note_detector::report;
+use wav_note_detector::tuner::{self, Reading, Tuner};
+use wav_note_detector::{Analyzer, AnalyzerConfig, Frame, NoteNamer};
+
+// Most frames read from stdin at a time. Reads return whatever has
+// arrived, so this only bounds the buffer.
+const BLOCK_FRAMES: usize = 512;
+
+// Analyze audio piped to stdin as it arrives, one output line per frame
+pub fn run(options: &Options) -> Result<Exit, Box<dyn Error>> {
+    let namer = namer(options);
+    listen(options, |out, frame, _, _| match options.format {
+        Format::Json => writeln!(out, "{}", report::frame_json(frame)),
+        _ => writeln!(out, "{}", frame_line(frame, &namer)),
+    })
+}
+
+// Tuner display of the audio piped to stdin, redrawn in place on a
+// terminal and printed panel after panel otherwise
+pub fn tuner(options: &Options) -> Result<Exit, Box<dyn Error>> {
+    let namer = namer(options);
+    let redraw = io::stdout().is_terminal();
+    let mut tuner = None;
+    let mut drawn = 0;
+    listen(options, |out, frame, config, sample_rate| {
+        let tuner = tuner.get_or_insert_with(|| {
+            let hop_seconds = config.hop_size as f32 / sample_rate;
+            Tuner::new(config.reference, options.smoothing, hop_seconds)
+        });
+        let reading = tuner.update(frame);
+        let panel = tuner_panel(reading, frame.time, config.reference, &namer, redraw);
+        if redraw {
+            // Back up over the last panel and overwrite it line by line
+            if drawn > 0 {
+                write!(out, "\x1b[{}A", drawn)?;
+            }
+            for line in &panel {
+                writeln!(out, "\x1b[2K{}", line)?;
+            }
+            drawn = panel.len();
+            Ok(())
+        } else {
+            writeln!(out, "{}\n", panel.join("\n"))
+        }
+    })
+}
+
+fn namer(options: &Options) -> NoteNamer {
+    match options.spelling {
+        KeySpelling::Fixed(spelling) => NoteNamer {
+            spelling,
+            ..options.namer
+        },
+        KeySpelling::Detected => options.namer,
+    }
+}
+
+// Feed stdin through the analyzer and hand every frame, with the resolved
+// configuration and the sample rate, to `on_frame` as soon as it completes
+fn listen<F>(options: &Options, mut on_frame: F) -> Result<Exit, Box<dyn Error>>
+where
+    F: FnMut(&mut io::StdoutLock, &Frame, &AnalyzerConfig, f32) -> io::Result<()>,
+{
+    let mut input = decode::open_input(io::stdin().lock(), options.raw, BLOCK_FRAMES)?;
+    let sample_rate = input.format().sample_rate as f32;
+    let channels = input.format().channels as usize;
+
+    let mut config = options.config.clone();
+    config.hop_size = options
+        .hop
+        .map_or(config.fft_size / 2, |hop| hop.samples(sample_rate));
+    if let Reference::Fixed(a4) = options.reference {
+        config.reference = a4;
+    }
+    eprintln!(
+        "Reading stdin: {} Hz, {} channel(s), {} ms per frame, {} ms hop",
+        sample_rate,
+        channels,
+        (config.fft_size as f32 / sample_rate * 1000.0).round(),
+        (config.hop_size as f32 / sample_rate * 1000.0).round()
+    );
+
+    let analyzer = Analyzer::new(config.clone());
+    let mut stream = analyzer.stream(sample_rate);
+    let mut out = io::stdout().lock();
+    let mut voiced = false;
+    while let Some(block) = input.next_block()? {
+        let mut signal = channels::select(&block, channels, options.mode)?;
+        for frame in stream.push(&signal.remove(0).samples) {
+            voiced |= frame.note.is_some();
+            // A closed pipe (e.g. `| head`) ends the readout, not an error
+            match on_frame(&mut out, &frame, &config, sample_rate).and_then(|_| out.flush()) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(Exit::Ok),
+                result => result?,
+            }
+        }
+    }
+    Ok(if voiced { Exit::Ok } else { Exit::NoNotes })
+}
+
+fn frame_line(frame: &Frame, namer: &NoteNamer) -> String {
+    match frame.note {
+        Some(note) => format!(
+            "{:>9.3} s  {:<5} {:>9.2} Hz {:>+7.1} cents",
+            frame.time,
+            namer.name(note),
+            frame.freq,
+            frame.cents
+        ),
+        None => format!("{:>9.3} s  -", frame.time),
+    }
+}
+
+// Large note name, frequency and cents, the meter and the reference
+fn tuner_panel(
+    reading: Option<Reading>,
+    time: f32,
+    reference: f32,
+    namer: &NoteNamer,
+    terminal: bool,
+) -> Vec<String> {
+    let fill = if terminal { '\u{2588}' } else { '#' };
+    let mut lines = Vec::new();
+    match reading {
+        Some(reading) => {
+            let name = namer.name(reading.note);
+            lines.extend(
+                tuner::big_text(&name, fill)
+                    .into_iter()
+                    .map(|row| format!("  {}", row)),
+            );
+            let verdict = match reading.cents {
+                c if c.abs() < 5.0 => "in tune",
+                c if c > 0.0 => "sharp",
+                _ => "flat",
+            };
+            lines.push(String::new());
+            lines.push(format!(
+                "  {}  {:.2} Hz  {:+.1} cents  {}",
+                name, reading.freq, reading.cents, verdict
+            ));
+            lines.extend(tuner::meter(reading.cents));
+        }
+        None => {
+            lines.extend(
+                tuner::big_text("-", fill)
+                    .into_iter()
+                    .map(|row| format!("  {}", row)),
+            );
+            lines.push(String::new());
+            lines.push("  no pitch".to_string());
+            let [labels, scale, _] = tuner::meter(0.0);
+            lines.extend([labels, scale, String::new()]);
+        }
+    }
+    lines.push(format!("  A4 = {:.2} Hz  {:.2} s", reference, time));
+    lines
+}
@@ -1,115 +1,879 @@
-use hound;
-use rustfft::{FftPlanner, num_complex::Complex};
-use std::collections::HashMap;
+mod cli;
+mod live;
+
+use cli::{Command, Exit, Format, Invocation, KeySpelling, Options, Reference};
+use hound::WavSpec;
+use std::collections::BTreeMap;
 use std::env;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use wav_note_detector::batch;
+use wav_note_detector::channels;
+use wav_note_detector::chords::{self, ChordSegment};
+use wav_note_detector::chroma::{self, Chromagram, chroma_vector};
+use wav_note_detector::decode::SampleReader;
+use wav_note_detector::json::Json;
+use wav_note_detector::key::{self, KeyScore};
+use wav_note_detector::midi::{self, MidiTrack};
+use wav_note_detector::png;
+use wav_note_detector::report::{self, SignalReport};
+use wav_note_detector::table::{self, Delimiter};
+use wav_note_detector::tuning::{self, TuningEstimate};
+use wav_note_detector::{
+    A4, Analysis, Analyzer, AnalyzerConfig, Chord, Key, NoteEvent, NoteNamer, Spelling, segment,
+};
 
-// Custom Hann window function, for filtering out noise
-fn hann(i: usize, size: usize) -> f32 {
-    let pi = std::f32::consts::PI;
-    (pi * i as f32 / (size as f32)).sin().powi(2)
+// Analysis of one signal of a file
+struct SignalResult {
+    label: String,
+    analysis: Analysis,
+    events: Vec<NoteEvent>,
+    // Only computed for the chroma and chords commands
+    chroma: Option<Chromagram>,
+    chords: Vec<ChordSegment>,
 }
 
-// Convert frequency to MIDI note number (A4 is 69)
-fn freq_to_midi_note(freq: f32) -> Option<u8> {
-    if freq <= 0.0 {
-        return None;
-    }
-    let note_num = 69.0 + 12.0 * (freq / 440.0).log2();
-    if note_num < 0.0 || note_num > 127.0 {
-        None
-    } else {
-        Some(note_num.round() as u8)
+// Everything computed for one input file
+struct FileResult {
+    path: String,
+    spec: WavSpec,
+    duration: f32,
+    // Analysis parameters with the hop resolved for this file's sample rate
+    config: AnalyzerConfig,
+    signals: Vec<SignalResult>,
+    // Note naming with the spelling resolved for this file
+    namer: NoteNamer,
+}
+
+impl FileResult {
+    fn has_notes(&self) -> bool {
+        self.signals
+            .iter()
+            .any(|s| !s.analysis.note_counts.is_empty())
     }
 }
 
-// Convert MIDI note number to note name (69 -> A4, 60 -> C4, etc.)
-fn midi_note_to_name(note: u8) -> String {
-    let note_names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
-    let octave = (note / 12).saturating_sub(1);
-    let note_index = (note % 12) as usize;
-    format!("{}{}", note_names[note_index], octave)
+// An input that could not be processed
+struct Failure {
+    path: String,
+    error: String,
+    exit: Exit,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <input.wav>", args[0]);
-        std::process::exit(1);
+// Frames per block read from disk; analysis keeps only a window of samples
+const BLOCK_FRAMES: usize = 65536;
+
+// One signal of a file analyzed while streaming
+struct StreamedSignal {
+    label: String,
+    analysis: Analysis,
+    chroma: Option<Chromagram>,
+}
+
+// Read a file block by block and feed every selected signal to its own
+// analysis stream, so memory use does not grow with the file's length
+fn stream_file(
+    path: &Path,
+    options: &Options,
+    config: &AnalyzerConfig,
+    with_chroma: bool,
+) -> Result<Vec<StreamedSignal>, Box<dyn Error>> {
+    let mut reader = SampleReader::open(path, BLOCK_FRAMES)?;
+    let channels = reader.channels();
+    let sample_rate = reader.spec().sample_rate as f32;
+    let analyzer = Analyzer::new(config.clone());
+
+    // Selecting from no samples checks the channel mode and gives the labels
+    let labels: Vec<String> = channels::select(&[], channels, options.mode)?
+        .into_iter()
+        .map(|signal| signal.label)
+        .collect();
+    let mut streams: Vec<_> = labels
+        .iter()
+        .map(|_| {
+            let chroma = with_chroma.then(|| Chromagram {
+                sample_rate,
+                fft_size: config.fft_size,
+                hop_size: config.hop_size.max(1),
+                frames: Vec::new(),
+                energy: Vec::new(),
+            });
+            (analyzer.stream(sample_rate), Vec::new(), chroma)
+        })
+        .collect();
+
+    while let Some(block) = reader.next_block()? {
+        let signals = channels::select(&block, channels, options.mode)?;
+        for ((stream, frames, chroma), signal) in streams.iter_mut().zip(signals) {
+            stream.push_with(&signal.samples, |frame, input| {
+                frames.push(frame);
+                if let Some(chroma) = chroma.as_mut() {
+                    let (vector, energy) = chroma_vector(
+                        input.spectrum,
+                        input.sample_rate,
+                        input.fft_size,
+                        config.reference,
+                        &options.chroma,
+                    );
+                    chroma.frames.push(vector);
+                    chroma.energy.push(energy);
+                }
+            });
+        }
     }
-    let filename = &args[1];
 
-    // Open the WAV file
-    let mut reader = hound::WavReader::open(filename)?;
-    let spec = reader.spec();
+    Ok(labels
+        .into_iter()
+        .zip(streams)
+        .map(|(label, (stream, frames, chroma))| StreamedSignal {
+            label,
+            analysis: stream.into_analysis(frames),
+            chroma,
+        })
+        .collect())
+}
+
+fn analyze_file(path: &Path, options: &Options) -> Result<FileResult, Box<dyn Error>> {
+    let (_, spec, duration) = file_info(path)?;
     let sample_rate = spec.sample_rate as f32;
-    // Read samples into a vector
-    let samples: Vec<f32> = reader
-        .samples::<i16>()
-        .filter_map(Result::ok)
-        .map(|s| s as f32 / i16::MAX as f32)
-        .collect();
 
-    // Get wav duration in seconds
-    let duration_seconds = samples.len() as f32 / sample_rate;
-    println!("File: {}", filename);
-    println!("Sample rate: {} Hz", sample_rate);
-    println!("Duration: {:.2} seconds", duration_seconds);
+    let mut config = options.config.clone();
+    config.hop_size = options
+        .hop
+        .map_or(config.fft_size / 2, |hop| hop.samples(sample_rate));
 
-    // FFT parameters
-    let fft_size = 4096;
-    let hop_size = fft_size / 2;
+    let with_chroma = matches!(options.command, Command::Chroma | Command::Chords);
+    let signals = match options.reference {
+        Reference::Fixed(a4) => {
+            config.reference = a4;
+            stream_file(path, options, &config, with_chroma)?
+        }
+        // Analyze at concert pitch, then again against the estimated tuning
+        Reference::Auto => {
+            config.reference = A4;
+            let signals = stream_file(path, options, &config, with_chroma)?;
+            let analyses: Vec<&Analysis> = signals.iter().map(|s| &s.analysis).collect();
+            match tuning::estimate(&analyses) {
+                Some(estimate) => {
+                    config.reference = estimate.reference(A4);
+                    stream_file(path, options, &config, with_chroma)?
+                }
+                None => signals,
+            }
+        }
+    };
+
+    let signals = signals
+        .into_iter()
+        .map(|signal| {
+            let events = segment(&signal.analysis);
+            let chords = match (&signal.chroma, options.command) {
+                (Some(chromagram), Command::Chords) => chords::recognize(chromagram),
+                _ => Vec::new(),
+            };
+            SignalResult {
+                label: signal.label,
+                analysis: signal.analysis,
+                events,
+                chroma: signal.chroma,
+                chords,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let spelling = match options.spelling {
+        KeySpelling::Fixed(spelling) => spelling,
+        KeySpelling::Detected => {
+            let analyses: Vec<&Analysis> = signals.iter().map(|s| &s.analysis).collect();
+            let profile = key::pitch_class_profile(&analyses);
+            key::detect_key(&profile, options.key_profile)
+                .first()
+                .map_or(Spelling::Sharps, |best| Spelling::Key(best.key))
+        }
+    };
 
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(fft_size);
-    let window: Vec<f32> = (0..fft_size).map(|i| hann(i, fft_size)).collect();
-    let mut buffer: Vec<Complex<f32>> = vec![Complex::new(0.0, 0.0); fft_size];
+    Ok(FileResult {
+        path: path.display().to_string(),
+        spec,
+        duration,
+        config,
+        signals,
+        namer: NoteNamer {
+            spelling,
+            ..options.namer
+        },
+    })
+}
+
+// Only the header is read, so this is cheap even for long files
+fn file_info(path: &Path) -> Result<(String, WavSpec, f32), Box<dyn Error>> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let duration = reader.duration() as f32 / spec.sample_rate as f32;
+    Ok((path.display().to_string(), spec, duration))
+}
 
-    // Frequency count map: MIDI note -> count
-    let mut note_counts: HashMap<u8, usize> = HashMap::new();
+fn print_header(out: &mut impl Write, file: &FileResult) -> io::Result<()> {
+    writeln!(out, "File: {}", file.path)?;
+    writeln!(out, "Sample rate: {} Hz", file.spec.sample_rate)?;
+    writeln!(out, "Channels: {}", file.spec.channels)?;
+    writeln!(out, "Duration: {:.2} seconds", file.duration)?;
+    writeln!(
+        out,
+        "FFT size: {} (x{} zero padding), hop: {} samples, window: {}",
+        file.config.fft_size, file.config.zero_padding, file.config.hop_size, file.config.window
+    )?;
+    writeln!(out, "Reference: A4 = {:.2} Hz", file.config.reference)?;
+    Ok(())
+}
 
-    println!("Processing...");
+fn print_histogram(
+    out: &mut impl Write,
+    label: Option<&str>,
+    analysis: &Analysis,
+    namer: &NoteNamer,
+) -> io::Result<()> {
+    match label {
+        Some(label) => writeln!(out, "\nMost common notes detected ({}):", label)?,
+        None => writeln!(out, "\nMost common notes detected:")?,
+    }
+    for (note, count) in analysis.note_counts.iter().take(10) {
+        let name = namer.name(*note);
+        let (freq, cents) = analysis.note_pitch(*note).unwrap_or_default();
+        writeln!(
+            out,
+            "{}: {} occurrences ({:.2} Hz, {:+.1} cents)",
+            name, count, freq, cents
+        )?;
+    }
+    Ok(())
+}
 
-    // Sliding window FFT
-    for start in (0..samples.len().saturating_sub(fft_size)).step_by(hop_size) {
-        for i in 0..fft_size {
-            buffer[i].re = samples[start + i] * window[i];
-            buffer[i].im = 0.0;
+fn print_timeline(
+    out: &mut impl Write,
+    label: Option<&str>,
+    events: &[NoteEvent],
+    namer: &NoteNamer,
+) -> io::Result<()> {
+    match label {
+        Some(label) => writeln!(out, "\nNote timeline ({}):", label)?,
+        None => writeln!(out, "\nNote timeline:")?,
+    }
+    for event in events {
+        writeln!(
+            out,
+            "{:>8.2} - {:>8.2} s  {:<4} ({:.2} s, amplitude {:.2})",
+            event.start,
+            event.end,
+            namer.name(event.note),
+            event.duration(),
+            event.mean_amplitude
+        )?;
+    }
+    Ok(())
+}
+
+// Human readable summary of one analyzed file
+fn print_analysis(out: &mut impl Write, file: &FileResult) -> io::Result<()> {
+    print_header(out, file)?;
+    for signal in &file.signals {
+        let label = (file.signals.len() > 1).then_some(signal.label.as_str());
+        print_histogram(out, label, &signal.analysis, &file.namer)?;
+
+        let onsets: Vec<String> = signal
+            .analysis
+            .onsets
+            .iter()
+            .map(|o| format!("{:.2}", o.time))
+            .collect();
+        writeln!(out, "\nOnsets (s): {}", onsets.join(" "))?;
+
+        print_timeline(out, label, &signal.events, &file.namer)?;
+    }
+    Ok(())
+}
+
+fn print_track(out: &mut impl Write, file: &FileResult) -> io::Result<()> {
+    print_header(out, file)?;
+    for signal in &file.signals {
+        if file.signals.len() > 1 {
+            writeln!(out, "\n{}:", signal.label)?;
+        }
+        writeln!(
+            out,
+            "\n{:>6} {:>9} {:>10} {:<5} {:>7} {:>10} {:>6}",
+            "frame", "time", "freq", "note", "cents", "magnitude", "conf"
+        )?;
+        for frame in &signal.analysis.frames {
+            let (freq, name, cents) = match frame.note {
+                Some(note) => (
+                    format!("{:.2}", frame.freq),
+                    file.namer.name(note),
+                    format!("{:+.1}", frame.cents),
+                ),
+                None => ("-".to_string(), "-".to_string(), "-".to_string()),
+            };
+            writeln!(
+                out,
+                "{:>6} {:>9.3} {:>10} {:<5} {:>7} {:>10.3} {:>6.2}",
+                frame.index, frame.time, freq, name, cents, frame.magnitude, frame.confidence
+            )?;
         }
+    }
+    Ok(())
+}
 
-        fft.process(&mut buffer);
+// Tuning of a file over all of its signals
+fn file_tuning(file: &FileResult) -> Option<TuningEstimate> {
+    let analyses: Vec<&Analysis> = file.signals.iter().map(|s| &s.analysis).collect();
+    tuning::estimate(&analyses)
+}
 
-        let mut max_mag = 0.0;
-        let mut max_bin = 0;
-        for i in 1..(fft_size / 2) {
-            let mag = buffer[i].norm();
-            if mag > max_mag {
-                max_mag = mag;
-                max_bin = i;
-            }
+fn print_tuning(
+    out: &mut impl Write,
+    estimate: Option<&TuningEstimate>,
+    reference: f32,
+) -> io::Result<()> {
+    let Some(estimate) = estimate else {
+        writeln!(out, "\nNo notes detected, tuning unknown")?;
+        return Ok(());
+    };
+    writeln!(
+        out,
+        "\nTuning: {:+.1} cents (A4 = {:.2} Hz), confidence {:.2} over {} notes",
+        estimate.offset,
+        estimate.reference(reference),
+        estimate.confidence,
+        estimate.count
+    )?;
+
+    // Histogram in 5 cent groups, bars scaled to the fullest group
+    writeln!(out, "\nDeviation histogram (cents):")?;
+    let groups: Vec<usize> = estimate
+        .histogram
+        .chunks(5)
+        .map(|c| c.iter().sum())
+        .collect();
+    let max = groups.iter().copied().max().unwrap_or(0).max(1);
+    for (i, &count) in groups.iter().enumerate() {
+        let low = i as i32 * 5 - 50;
+        writeln!(
+            out,
+            "{:>+4} to {:>+4}  {:<40} {}",
+            low,
+            low + 5,
+            "#".repeat(count * 40 / max),
+            count
+        )?;
+    }
+    Ok(())
+}
+
+// Chord symbol with the root spelled by `namer`, N for no chord
+fn chord_name(chord: Option<Chord>, namer: &NoteNamer) -> String {
+    chord.map_or("N".to_string(), |c| {
+        format!("{}:{}", namer.pitch_class(c.root), c.quality)
+    })
+}
+
+// Key with its tonic spelled as in the key itself (Eb minor, not D# minor)
+fn key_name(key: Key, namer: &NoteNamer) -> String {
+    let namer = NoteNamer {
+        spelling: Spelling::Key(key),
+        ..*namer
+    };
+    format!("{} {}", namer.pitch_class(key.tonic), key.mode)
+}
+
+fn print_keys(out: &mut impl Write, scores: &[KeyScore], namer: &NoteNamer) -> io::Result<()> {
+    let Some(best) = scores.first() else {
+        writeln!(out, "\nNo notes detected, key unknown")?;
+        return Ok(());
+    };
+    writeln!(
+        out,
+        "\nKey: {} (correlation {:.3})",
+        key_name(best.key, namer),
+        best.correlation
+    )?;
+    writeln!(out, "\nRunner-ups:")?;
+    for score in scores.iter().skip(1).take(4) {
+        writeln!(
+            out,
+            "{:<10} {:.3}",
+            key_name(score.key, namer),
+            score.correlation
+        )?;
+    }
+    Ok(())
+}
+
+// Chromagram as text, one row per pitch class with B at the top and one
+// column per frame
+fn print_chroma(
+    out: &mut impl Write,
+    label: Option<&str>,
+    chromagram: &Chromagram,
+    namer: &NoteNamer,
+) -> io::Result<()> {
+    const SHADES: [char; 5] = [' ', '.', ':', '*', '#'];
+    match label {
+        Some(label) => writeln!(out, "\nChromagram ({}):", label)?,
+        None => writeln!(out, "\nChromagram:")?,
+    }
+    for pitch_class in (0..12).rev() {
+        let row: String = chromagram
+            .frames
+            .iter()
+            .map(|chroma| {
+                let shade = (chroma[pitch_class] * (SHADES.len() - 1) as f32).round();
+                SHADES[shade as usize]
+            })
+            .collect();
+        writeln!(out, "{:<2} |{}", namer.pitch_class(pitch_class as u8), row)?;
+    }
+    Ok(())
+}
+
+fn print_info(
+    out: &mut impl Write,
+    infos: &[(String, WavSpec, f32)],
+    format: Format,
+) -> io::Result<()> {
+    if format == Format::Json {
+        let files: Vec<Json> = infos
+            .iter()
+            .map(|(path, spec, duration)| report::file_json(path, spec, *duration))
+            .collect();
+        let json = Json::object()
+            .field("schema", "wav_note_detector.info")
+            .field("version", report::SCHEMA_VERSION)
+            .field("files", files);
+        writeln!(out, "{}", json.pretty())?;
+        return Ok(());
+    }
+    for (path, spec, duration) in infos {
+        let kind = match spec.sample_format {
+            hound::SampleFormat::Int => "integer",
+            hound::SampleFormat::Float => "float",
+        };
+        writeln!(out, "File: {}", path)?;
+        writeln!(out, "Sample rate: {} Hz", spec.sample_rate)?;
+        writeln!(out, "Channels: {}", spec.channels)?;
+        writeln!(out, "Format: {}-bit {}", spec.bits_per_sample, kind)?;
+        writeln!(out, "Duration: {:.2} seconds\n", duration)?;
+    }
+    Ok(())
+}
+
+// Path of a file and views of its signals for the report builders
+fn signal_reports(file: &FileResult) -> (&str, Vec<SignalReport<'_>>) {
+    let reports = file
+        .signals
+        .iter()
+        .map(|signal| SignalReport {
+            label: &signal.label,
+            analysis: &signal.analysis,
+            events: &signal.events,
+            chroma: signal.chroma.as_ref(),
+            chords: &signal.chords,
+        })
+        .collect();
+    (&file.path, reports)
+}
+
+fn file_json(file: &FileResult, options: &Options) -> Json {
+    let (path, reports) = signal_reports(file);
+    report::json_report(
+        path,
+        &file.spec,
+        file.duration,
+        options.mode,
+        &file.config,
+        &reports,
+    )
+}
+
+// Note counts summed over every signal of every file, most common first
+fn aggregate_counts(files: &[FileResult]) -> Vec<(u8, usize)> {
+    let mut totals: BTreeMap<u8, usize> = BTreeMap::new();
+    for signal in files.iter().flat_map(|f| &f.signals) {
+        for &(note, count) in &signal.analysis.note_counts {
+            *totals.entry(note).or_insert(0) += count;
         }
+    }
+    let mut counts: Vec<(u8, usize)> = totals.into_iter().collect();
+    counts.sort_by_key(|&(note, count)| (std::cmp::Reverse(count), note));
+    counts
+}
 
-        let freq = max_bin as f32 * sample_rate / fft_size as f32;
+// Signals of a file as pitch track table sources, prefixed with the file
+// path when several files share one table
+fn track_rows(file: &FileResult, with_path: bool) -> Vec<(String, &Analysis)> {
+    file.signals
+        .iter()
+        .map(|signal| {
+            let label = if with_path {
+                format!("{}:{}", file.path, signal.label)
+            } else {
+                signal.label.clone()
+            };
+            (label, &signal.analysis)
+        })
+        .collect()
+}
 
-        // Filter out very low frequencies and low magnitude noise
-        if freq < 20.0 || max_mag < 0.01 {
-            continue;
+// Write the MIDI, pitch track and heat map exports of a single file
+fn write_exports(file: &FileResult, options: &Options) -> Result<(), Box<dyn Error>> {
+    for (path, delimiter) in &options.tables {
+        let rows = track_rows(file, false);
+        let rows: Vec<(&str, &Analysis)> = rows.iter().map(|(l, a)| (l.as_str(), *a)).collect();
+        table::write_pitch_track_file(path, &rows, *delimiter)?;
+        eprintln!("Wrote pitch track to {}", path);
+    }
+
+    if let Some(path) = &options.png_path {
+        let chromagrams: Vec<&Chromagram> = file
+            .signals
+            .iter()
+            .filter_map(|s| s.chroma.as_ref())
+            .collect();
+        png::write_png_file(path, &chroma::heat_map(&chromagrams))?;
+        eprintln!("Wrote chroma heat map to {}", path);
+    }
+
+    if let Some(path) = &options.midi_path {
+        let tracks: Vec<MidiTrack> = file
+            .signals
+            .iter()
+            .map(|signal| MidiTrack {
+                name: &signal.label,
+                events: &signal.events,
+            })
+            .collect();
+        midi::write_midi_file(path, &tracks, &options.midi_options)?;
+        eprintln!("Wrote MIDI to {}", path);
+    }
+    Ok(())
+}
+
+// A closed pipe (e.g. `| head`) cuts the output short but is not an error
+fn ignore_broken_pipe(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+// Print `text` as the whole output, as for --help and --version
+fn print_text(text: &str) -> Result<Exit, Box<dyn Error>> {
+    let mut out = io::stdout().lock();
+    ignore_broken_pipe(writeln!(out, "{}", text).and_then(|_| out.flush()))?;
+    Ok(Exit::Ok)
+}
+
+fn print_failures(failures: &[Failure]) {
+    if failures.is_empty() {
+        return;
+    }
+    eprintln!("\nFailed ({}):", failures.len());
+    for failure in failures {
+        eprintln!("  {}: {}", failure.path, failure.error);
+    }
+}
+
+// Resolve the inputs and run `work` on every file in parallel
+fn process<T, F>(options: &Options, work: F) -> (Vec<T>, Vec<Failure>)
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T, Box<dyn Error>> + Sync,
+{
+    let (paths, input_errors) = batch::expand_inputs(&options.inputs);
+    let mut failures: Vec<Failure> = input_errors
+        .into_iter()
+        .map(|e| Failure {
+            path: e.input,
+            exit: Exit::classify(&e.error),
+            error: e.error.to_string(),
+        })
+        .collect();
+
+    let outcomes = batch::parallel_map(&paths, options.jobs, |path: &PathBuf| {
+        // Errors aren't Send, so classify them on the worker thread
+        work(path).map_err(|e| (Exit::classify(e.as_ref()), e.to_string()))
+    });
+
+    let mut done = Vec::new();
+    for (path, outcome) in paths.iter().zip(outcomes) {
+        match outcome {
+            Ok(result) => done.push(result),
+            Err((exit, error)) => failures.push(Failure {
+                path: path.display().to_string(),
+                error,
+                exit,
+            }),
         }
+    }
+    (done, failures)
+}
 
-        if let Some(midi_note) = freq_to_midi_note(freq) {
-            // Count the note
-            *note_counts.entry(midi_note).or_insert(0) += 1;
+// The command's report on the analyzed files, on stdout
+fn print_report(
+    out: &mut impl Write,
+    options: &Options,
+    files: &[FileResult],
+    failures: &[Failure],
+    single: bool,
+) -> io::Result<()> {
+    match (options.command, options.format) {
+        (Command::Analyze, Format::Json) if single && failures.is_empty() => {
+            writeln!(out, "{}", file_json(&files[0], options).pretty())?;
+        }
+        (Command::Analyze, Format::Json) => {
+            let reports = files.iter().map(|f| file_json(f, options)).collect();
+            let failures: Vec<(String, String)> = failures
+                .iter()
+                .map(|f| (f.path.clone(), f.error.clone()))
+                .collect();
+            let json = report::batch_report(reports, &aggregate_counts(files), &failures);
+            writeln!(out, "{}", json.pretty())?;
+        }
+        (Command::Analyze, _) => {
+            for file in files {
+                writeln!(out)?;
+                print_analysis(out, file)?;
+            }
+            if files.len() > 1 {
+                writeln!(out, "\nAggregate over {} files:", files.len())?;
+                for (note, count) in aggregate_counts(files).iter().take(10) {
+                    writeln!(out, "{}: {} occurrences", options.namer.name(*note), count)?;
+                }
+            }
+        }
+        (Command::Track, Format::Json) => {
+            let tracks: Vec<_> = files.iter().map(signal_reports).collect();
+            writeln!(out, "{}", report::track_report(&tracks).pretty())?;
         }
+        (Command::Track, Format::Csv | Format::Tsv) => {
+            let delimiter = match options.format {
+                Format::Csv => Delimiter::Comma,
+                _ => Delimiter::Tab,
+            };
+            let rows: Vec<(String, &Analysis)> = files
+                .iter()
+                .flat_map(|f| track_rows(f, files.len() > 1))
+                .collect();
+            let rows: Vec<(&str, &Analysis)> = rows.iter().map(|(l, a)| (l.as_str(), *a)).collect();
+            table::write_pitch_track(out, &rows, delimiter)?;
+        }
+        (Command::Track, _) => {
+            for file in files {
+                writeln!(out)?;
+                print_track(out, file)?;
+            }
+        }
+        (Command::Transcribe, Format::Json) => {
+            let transcriptions: Vec<_> = files.iter().map(signal_reports).collect();
+            writeln!(
+                out,
+                "{}",
+                report::transcription_report(&transcriptions).pretty()
+            )?;
+        }
+        (Command::Transcribe, _) => {
+            for file in files {
+                writeln!(out)?;
+                print_header(out, file)?;
+                for signal in &file.signals {
+                    let label = (file.signals.len() > 1).then_some(signal.label.as_str());
+                    print_timeline(out, label, &signal.events, &file.namer)?;
+                }
+            }
+        }
+        (Command::Tune, format) => {
+            let estimates: Vec<Option<TuningEstimate>> = files.iter().map(file_tuning).collect();
+            if format == Format::Json {
+                let entries: Vec<(&str, f32, Option<&TuningEstimate>)> = files
+                    .iter()
+                    .zip(&estimates)
+                    .map(|(f, e)| (f.path.as_str(), f.config.reference, e.as_ref()))
+                    .collect();
+                // cli::parse rejects --a4 auto for tune
+                let Reference::Fixed(reference) = options.reference else {
+                    unreachable!("tune needs a fixed reference")
+                };
+                let json = report::tuning_report(&entries, reference);
+                writeln!(out, "{}", json.pretty())?;
+            } else {
+                for (file, estimate) in files.iter().zip(&estimates) {
+                    writeln!(out)?;
+                    print_header(out, file)?;
+                    print_tuning(out, estimate.as_ref(), file.config.reference)?;
+                }
+            }
+        }
+        (Command::Key, format) => {
+            let keys: Vec<(&str, [f32; 12], Vec<KeyScore>)> = files
+                .iter()
+                .map(|file| {
+                    let analyses: Vec<&Analysis> =
+                        file.signals.iter().map(|s| &s.analysis).collect();
+                    let profile = key::pitch_class_profile(&analyses);
+                    let scores = key::detect_key(&profile, options.key_profile);
+                    (file.path.as_str(), profile, scores)
+                })
+                .collect();
+            if format == Format::Json {
+                writeln!(
+                    out,
+                    "{}",
+                    report::key_report(&keys, options.key_profile).pretty()
+                )?;
+            } else {
+                for (file, (_, _, scores)) in files.iter().zip(&keys) {
+                    writeln!(out)?;
+                    print_header(out, file)?;
+                    print_keys(out, scores, &file.namer)?;
+                }
+            }
+        }
+        (Command::Chroma, Format::Json) => {
+            let chromas: Vec<_> = files.iter().map(signal_reports).collect();
+            writeln!(out, "{}", report::chroma_report(&chromas).pretty())?;
+        }
+        (Command::Chroma, Format::Csv | Format::Tsv) => {
+            let delimiter = match options.format {
+                Format::Csv => Delimiter::Comma,
+                _ => Delimiter::Tab,
+            };
+            let mut rows: Vec<(String, &Chromagram)> = Vec::new();
+            for file in files {
+                for signal in &file.signals {
+                    let label = if files.len() > 1 {
+                        format!("{}:{}", file.path, signal.label)
+                    } else {
+                        signal.label.clone()
+                    };
+                    rows.extend(signal.chroma.as_ref().map(|c| (label, c)));
+                }
+            }
+            let rows: Vec<(&str, &Chromagram)> =
+                rows.iter().map(|(l, c)| (l.as_str(), *c)).collect();
+            table::write_chroma(out, &rows, delimiter)?;
+        }
+        (Command::Chroma, _) => {
+            for file in files {
+                writeln!(out)?;
+                print_header(out, file)?;
+                for signal in &file.signals {
+                    if let Some(chromagram) = &signal.chroma {
+                        let label = (file.signals.len() > 1).then_some(signal.label.as_str());
+                        print_chroma(out, label, chromagram, &file.namer)?;
+                    }
+                }
+            }
+        }
+        (Command::Chords, Format::Json) => {
+            let chords: Vec<_> = files.iter().map(signal_reports).collect();
+            writeln!(out, "{}", report::chords_report(&chords).pretty())?;
+        }
+        (Command::Chords, _) => {
+            for file in files {
+                writeln!(out)?;
+                print_header(out, file)?;
+                for signal in &file.signals {
+                    match file.signals.len() {
+                        1 => writeln!(out, "\nChords:")?,
+                        _ => writeln!(out, "\nChords ({}):", signal.label)?,
+                    }
+                    for segment in &signal.chords {
+                        writeln!(
+                            out,
+                            "{:.2}\u{2013}{:.2} {}",
+                            segment.start,
+                            segment.end,
+                            chord_name(segment.chord, &file.namer)
+                        )?;
+                    }
+                }
+            }
+        }
+        (Command::Info | Command::Live | Command::Tuner, _) => unreachable!("handled above"),
     }
+    Ok(())
+}
 
-    // Sort notes by frequency descending
-    let mut counts_vec: Vec<(u8, usize)> = note_counts.into_iter().collect();
-    counts_vec.sort_by(|a, b| b.1.cmp(&a.1));
+fn run(options: &Options) -> Result<Exit, Box<dyn Error>> {
+    match options.command {
+        Command::Live => return live::run(options),
+        Command::Tuner => return live::tuner(options),
+        _ => {}
+    }
 
-    println!("\nMost common notes detected:");
-    for (note, count) in counts_vec.iter().take(10) {
-        let name = midi_note_to_name(*note);
-        println!("{}: {} occurrences", name, count);
+    // One plain file, as opposed to a directory, glob or several inputs
+    let single = matches!(options.inputs.as_slice(),
+        [input] if !batch::is_glob(input) && !Path::new(input).is_dir());
+    let exports = options.midi_path.is_some() || options.png_path.is_some();
+    if !single && (exports || !options.tables.is_empty()) {
+        eprintln!("error: --midi, --png, --csv and --tsv need exactly one input file");
+        return Ok(Exit::Usage);
     }
 
-    Ok(())
+    if options.command == Command::Info {
+        let (infos, failures) = process(options, file_info);
+        let mut out = io::stdout().lock();
+        ignore_broken_pipe(print_info(&mut out, &infos, options.format).and_then(|_| out.flush()))?;
+        print_failures(&failures);
+        return Ok(failures.iter().map(|f| f.exit).max().unwrap_or(Exit::Ok));
+    }
+
+    let (files, failures) = process(options, |path| analyze_file(path, options));
+    if single && let Some(file) = files.first() {
+        write_exports(file, options)?;
+    }
+
+    let mut out = io::stdout().lock();
+    let written =
+        print_report(&mut out, options, &files, &failures, single).and_then(|_| out.flush());
+    ignore_broken_pipe(written)?;
+
+    print_failures(&failures);
+    let worst = failures.iter().map(|f| f.exit).max();
+    Ok(match worst {
+        Some(exit) => exit,
+        None if !files.iter().any(FileResult::has_notes) => Exit::NoNotes,
+        None => Exit::Ok,
+    })
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let program = args
+        .first()
+        .and_then(|arg| Path::new(arg).file_name())
+        .map_or("wav_note_detector".to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        });
+
+    let result = match cli::parse(args.get(1..).unwrap_or_default()) {
+        Ok(Invocation::Run(options)) => run(&options),
+        Ok(Invocation::Help(command)) => print_text(&cli::help(&program, command)),
+        Ok(Invocation::Version) => {
+            print_text(&format!("{} {}", program, env!("CARGO_PKG_VERSION")))
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\nRun '{} --help' for usage.", e, program);
+            return Exit::Usage.into();
+        }
+    };
+
+    match result {
+        Ok(exit) => exit.into(),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            Exit::classify(e.as_ref()).into()
+        }
+    }
 }
@@ -0,0 +1,330 @@
+use crate::segment::NoteEvent;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+// Dynamic range, in dB below the loudest note, mapped onto velocities 1..=127
+const VELOCITY_RANGE_DB: f32 = 48.0;
+
+// Standard MIDI File layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MidiFormat {
+    // Format 0: everything in one track, one MIDI channel per source
+    SingleTrack,
+    // Format 1: a tempo track followed by one track per source
+    #[default]
+    MultiTrack,
+}
+
+impl FromStr for MidiFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(MidiFormat::SingleTrack),
+            "1" => Ok(MidiFormat::MultiTrack),
+            _ => Err(format!("invalid MIDI format '{}' (expected 0 or 1)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MidiOptions {
+    pub format: MidiFormat,
+    pub tempo_bpm: f32,
+    // Ticks per quarter note
+    pub ppq: u16,
+}
+
+impl Default for MidiOptions {
+    fn default() -> Self {
+        MidiOptions {
+            format: MidiFormat::default(),
+            tempo_bpm: 120.0,
+            ppq: 480,
+        }
+    }
+}
+
+// Microseconds per quarter note at `bpm`, if a tempo event's 24 bits hold it
+pub fn tempo_micros(bpm: f32) -> Option<u32> {
+    let micros = (60_000_000.0 / bpm as f64).round();
+    (bpm > 0.0 && bpm.is_finite() && micros <= 0xff_ffff as f64).then_some(micros as u32)
+}
+
+// Notes detected in one signal, written as one track / channel
+pub struct MidiTrack<'a> {
+    pub name: &'a str,
+    pub events: &'a [NoteEvent],
+}
+
+// Write note events to a Standard MIDI File on disk
+pub fn write_midi_file<P: AsRef<Path>>(
+    path: P,
+    tracks: &[MidiTrack],
+    options: &MidiOptions,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_midi(&mut out, tracks, options)?;
+    out.flush()
+}
+
+// Write note events as a Standard MIDI File
+pub fn write_midi<W: Write>(
+    out: &mut W,
+    tracks: &[MidiTrack],
+    options: &MidiOptions,
+) -> io::Result<()> {
+    let Some(micros) =
+        tempo_micros(options.tempo_bpm).filter(|_| (1..=0x7fff).contains(&options.ppq))
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "tempo must be at least 3.58 bpm and PPQ within 1..=32767",
+        ));
+    };
+    let loudest = tracks
+        .iter()
+        .flat_map(|t| t.events)
+        .map(|e| e.mean_amplitude)
+        .fold(0.0, f32::max);
+    let ticks_per_second = options.tempo_bpm / 60.0 * options.ppq as f32;
+
+    // Note on/off messages per source: (tick, order, bytes). Offs sort before
+    // ons at the same tick so repeated notes don't cut each other short.
+    let messages: Vec<Vec<(u32, u8, [u8; 3])>> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let channel = (i % 16) as u8;
+            let mut messages = Vec::new();
+            for event in track.events {
+                let on = (event.start * ticks_per_second).round() as u32;
+                let off = ((event.end * ticks_per_second).round() as u32).max(on + 1);
+                let velocity = velocity(event.mean_amplitude, loudest);
+                messages.push((on, 1, [0x90 | channel, event.note & 0x7f, velocity]));
+                messages.push((off, 0, [0x80 | channel, event.note & 0x7f, 0]));
+            }
+            messages
+        })
+        .collect();
+
+    let mut chunks = Vec::new();
+    match options.format {
+        MidiFormat::SingleTrack => {
+            let mut track = TrackWriter::default();
+            track.tempo(micros);
+            let mut all: Vec<_> = messages.into_iter().flatten().collect();
+            all.sort_by_key(|&(tick, order, _)| (tick, order));
+            track.messages(&all);
+            chunks.push(track.finish());
+        }
+        MidiFormat::MultiTrack => {
+            let mut tempo = TrackWriter::default();
+            tempo.tempo(micros);
+            chunks.push(tempo.finish());
+            for (track, mut messages) in tracks.iter().zip(messages) {
+                let mut writer = TrackWriter::default();
+                writer.name(track.name);
+                messages.sort_by_key(|&(tick, order, _)| (tick, order));
+                writer.messages(&messages);
+                chunks.push(writer.finish());
+            }
+        }
+    }
+
+    let format: u16 = match options.format {
+        MidiFormat::SingleTrack => 0,
+        MidiFormat::MultiTrack => 1,
+    };
+    out.write_all(b"MThd")?;
+    out.write_all(&6u32.to_be_bytes())?;
+    out.write_all(&format.to_be_bytes())?;
+    out.write_all(&(chunks.len() as u16).to_be_bytes())?;
+    out.write_all(&options.ppq.to_be_bytes())?;
+    for chunk in chunks {
+        out.write_all(b"MTrk")?;
+        out.write_all(&(chunk.len() as u32).to_be_bytes())?;
+        out.write_all(&chunk)?;
+    }
+    Ok(())
+}
+
+// Map a note's magnitude to a velocity on a dB scale below the loudest note
+fn velocity(amplitude: f32, loudest: f32) -> u8 {
+    if amplitude <= 0.0 || loudest <= 0.0 {
+        return 1;
+    }
+    let db = 20.0 * (amplitude / loudest).log10();
+    let scaled = 1.0 + 126.0 * (1.0 + db / VELOCITY_RANGE_DB);
+    scaled.round().clamp(1.0, 127.0) as u8
+}
+
+// Builds the body of one MTrk chunk with delta times
+#[derive(Default)]
+struct TrackWriter {
+    data: Vec<u8>,
+    tick: u32,
+}
+
+impl TrackWriter {
+    fn delta(&mut self, tick: u32) {
+        write_vlq(&mut self.data, tick.saturating_sub(self.tick));
+        self.tick = self.tick.max(tick);
+    }
+
+    fn meta(&mut self, kind: u8, payload: &[u8]) {
+        self.delta(self.tick);
+        self.data.extend([0xff, kind]);
+        write_vlq(&mut self.data, payload.len() as u32);
+        self.data.extend_from_slice(payload);
+    }
+
+    fn tempo(&mut self, micros: u32) {
+        self.meta(0x51, &micros.to_be_bytes()[1..]);
+        // 4/4 time, 24 MIDI clocks per metronome click, 8 32nds per quarter
+        self.meta(0x58, &[4, 2, 24, 8]);
+    }
+
+    fn name(&mut self, name: &str) {
+        self.meta(0x03, name.as_bytes());
+    }
+
+    fn messages(&mut self, messages: &[(u32, u8, [u8; 3])]) {
+        for (tick, _, bytes) in messages {
+            self.delta(*tick);
+            self.data.extend_from_slice(bytes);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.meta(0x2f, &[]);
+        self.data
+    }
+}
+
+// MIDI variable length quantity: 7 bits per byte, high bit set on all but the last
+fn write_vlq(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = [0u8; 5];
+    let mut i = bytes.len() - 1;
+    bytes[i] = (value & 0x7f) as u8;
+    value >>= 7;
+    while value > 0 {
+        i -= 1;
+        bytes[i] = 0x80 | (value & 0x7f) as u8;
+        value >>= 7;
+    }
+    out.extend_from_slice(&bytes[i..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_length_quantities() {
+        let cases: [(u32, &[u8]); 7] = [
+            (0, &[0x00]),
+            (0x40, &[0x40]),
+            (0x7f, &[0x7f]),
+            (0x80, &[0x81, 0x00]),
+            (0x2000, &[0xc0, 0x00]),
+            (0x3fff, &[0xff, 0x7f]),
+            (0x0fff_ffff, &[0xff, 0xff, 0xff, 0x7f]),
+        ];
+        for (value, bytes) in cases {
+            let mut out = Vec::new();
+            write_vlq(&mut out, value);
+            assert_eq!(out, bytes, "{:#x}", value);
+        }
+    }
+
+    fn note(note: u8, start: f32, end: f32) -> NoteEvent {
+        NoteEvent {
+            note,
+            start,
+            end,
+            mean_amplitude: 1.0,
+        }
+    }
+
+    #[test]
+    fn header_and_tracks() {
+        let events = [note(60, 0.0, 0.5), note(64, 0.5, 1.0)];
+        let tracks = [
+            MidiTrack {
+                name: "left",
+                events: &events,
+            },
+            MidiTrack {
+                name: "right",
+                events: &events[..1],
+            },
+        ];
+        for (format, number, chunks) in [
+            (MidiFormat::SingleTrack, 0u16, 1u16),
+            (MidiFormat::MultiTrack, 1, 3),
+        ] {
+            let options = MidiOptions {
+                format,
+                ..MidiOptions::default()
+            };
+            let mut out = Vec::new();
+            write_midi(&mut out, &tracks, &options).unwrap();
+            assert_eq!(&out[..8], b"MThd\0\0\0\x06");
+            assert_eq!(out[8..10], number.to_be_bytes());
+            assert_eq!(out[10..12], chunks.to_be_bytes());
+            assert_eq!(out[12..14], 480u16.to_be_bytes());
+
+            // Every chunk is an MTrk whose length covers it exactly, ending
+            // with the end of track meta event
+            let mut rest = &out[14..];
+            for _ in 0..chunks {
+                assert_eq!(&rest[..4], b"MTrk");
+                let len = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+                assert_eq!(&rest[8 + len - 3..8 + len], [0xff, 0x2f, 0x00]);
+                rest = &rest[8 + len..];
+            }
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn invalid_tempo() {
+        // Below 60000000 / 0xffffff bpm a quarter note outgrows 24 bits
+        for tempo_bpm in [0.0, -60.0, 3.5, f32::NAN, f32::INFINITY] {
+            let options = MidiOptions {
+                tempo_bpm,
+                ..MidiOptions::default()
+            };
+            assert!(
+                write_midi(&mut Vec::new(), &[], &options).is_err(),
+                "{}",
+                tempo_bpm
+            );
+        }
+    }
+
+    #[test]
+    fn tempo_track() {
+        let mut out = Vec::new();
+        write_midi(&mut out, &[], &MidiOptions::default()).unwrap();
+        // 120 bpm is 500000 microseconds per quarter note
+        let tempo = [0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20];
+        assert_eq!(&out[22..29], tempo);
+    }
+
+    #[test]
+    fn slowest_tempo() {
+        assert_eq!(tempo_micros(3.58), Some(16_759_777));
+        assert_eq!(tempo_micros(3.576), None);
+        let options = MidiOptions {
+            tempo_bpm: 3.58,
+            ..MidiOptions::default()
+        };
+        let mut out = Vec::new();
+        write_midi(&mut out, &[], &options).unwrap();
+        assert_eq!(&out[22..29], [0x00, 0xff, 0x51, 0x03, 0xff, 0xbb, 0xe1]);
+    }
+}
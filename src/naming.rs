@@ -0,0 +1,361 @@
+use crate::key::{Key, Mode};
+use std::fmt;
+use std::str::FromStr;
+
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+// Pitch class of each natural letter
+const NATURALS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+// Letter index and accidental (+1 sharp, -1 flat) of every pitch class
+const SHARPS: [(usize, i8); 12] = [
+    (0, 0),
+    (0, 1),
+    (1, 0),
+    (1, 1),
+    (2, 0),
+    (3, 0),
+    (3, 1),
+    (4, 0),
+    (4, 1),
+    (5, 0),
+    (5, 1),
+    (6, 0),
+];
+const FLATS: [(usize, i8); 12] = [
+    (0, 0),
+    (1, -1),
+    (1, 0),
+    (2, -1),
+    (2, 0),
+    (3, 0),
+    (4, -1),
+    (4, 0),
+    (5, -1),
+    (5, 0),
+    (6, -1),
+    (6, 0),
+];
+// Conventional tonic of each major and minor key, the enharmonic with the
+// smaller key signature (Db major over C# major, F# minor over Gb minor)
+const MAJOR_TONICS: [(usize, i8); 12] = [
+    (0, 0),
+    (1, -1),
+    (1, 0),
+    (2, -1),
+    (2, 0),
+    (3, 0),
+    (3, 1),
+    (4, 0),
+    (5, -1),
+    (5, 0),
+    (6, -1),
+    (6, 0),
+];
+const MINOR_TONICS: [(usize, i8); 12] = [
+    (0, 0),
+    (0, 1),
+    (1, 0),
+    (2, -1),
+    (2, 0),
+    (3, 0),
+    (3, 1),
+    (4, 0),
+    (4, 1),
+    (5, 0),
+    (6, -1),
+    (6, 0),
+];
+const MAJOR_SCALE: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+const MINOR_SCALE: [u8; 7] = [0, 2, 3, 5, 7, 8, 10];
+
+// How pitch classes are spelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Spelling {
+    #[default]
+    Sharps,
+    Flats,
+    // Notes of the key's scale by their scale letters (Cb in Gb major),
+    // other notes sharpened or flattened to match the key signature
+    Key(Key),
+}
+
+impl FromStr for Spelling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sharps" | "sharp" => Ok(Spelling::Sharps),
+            "flats" | "flat" => Ok(Spelling::Flats),
+            _ => s.parse().map(Spelling::Key).map_err(|_| {
+                format!(
+                    "invalid spelling '{}' (expected sharps, flats or a key like Eb or F#m)",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+// Which octave number middle C (MIDI 60) gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Octaves {
+    // C4 is middle C
+    #[default]
+    Scientific,
+    // C3 is middle C, as on Yamaha instruments and in many DAWs
+    MiddleC3,
+    // c' is middle C, C is two octaves below, c'' an octave above
+    Helmholtz,
+}
+
+impl FromStr for Octaves {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scientific" | "c4" => Ok(Octaves::Scientific),
+            "c3" | "yamaha" => Ok(Octaves::MiddleC3),
+            "helmholtz" => Ok(Octaves::Helmholtz),
+            _ => Err(format!(
+                "invalid octave convention '{}' (expected scientific, c3 or helmholtz)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Octaves {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Octaves::Scientific => "scientific",
+            Octaves::MiddleC3 => "c3",
+            Octaves::Helmholtz => "helmholtz",
+        })
+    }
+}
+
+// Turns MIDI notes and pitch classes into names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoteNamer {
+    pub spelling: Spelling,
+    pub octaves: Octaves,
+    // ♯ and ♭ instead of # and b
+    pub unicode: bool,
+}
+
+impl NoteNamer {
+    // Name of a MIDI note with octave (70 -> A#4, Bb4, b♭' ...)
+    pub fn name(&self, note: u8) -> String {
+        let (letter, accidental) = self.spell(note % 12);
+        // The octave follows the letter, so B#3 and Cb4 straddle middle C
+        let octave = (note as i32 - accidental as i32).div_euclid(12) - 1;
+        let accidental = self.accidental(accidental);
+        match self.octaves {
+            Octaves::Scientific => format!("{}{}{}", LETTERS[letter], accidental, octave),
+            Octaves::MiddleC3 => format!("{}{}{}", LETTERS[letter], accidental, octave - 1),
+            Octaves::Helmholtz if octave >= 3 => format!(
+                "{}{}{}",
+                LETTERS[letter].to_ascii_lowercase(),
+                accidental,
+                "'".repeat((octave - 3) as usize)
+            ),
+            Octaves::Helmholtz => format!(
+                "{}{}{}",
+                LETTERS[letter],
+                accidental,
+                ",".repeat((2 - octave) as usize)
+            ),
+        }
+    }
+
+    // Name of a pitch class without octave
+    pub fn pitch_class(&self, pitch_class: u8) -> String {
+        let (letter, accidental) = self.spell(pitch_class % 12);
+        format!("{}{}", LETTERS[letter], self.accidental(accidental))
+    }
+
+    // Letter index into C D E F G A B and accidental of a pitch class
+    pub fn spell(&self, pitch_class: u8) -> (usize, i8) {
+        let pitch_class = pitch_class % 12;
+        match self.spelling {
+            Spelling::Sharps => SHARPS[pitch_class as usize],
+            Spelling::Flats => FLATS[pitch_class as usize],
+            Spelling::Key(key) => spell_in_key(pitch_class, key),
+        }
+    }
+
+    fn accidental(&self, accidental: i8) -> String {
+        let (sharp, flat) = if self.unicode {
+            ("\u{266f}", "\u{266d}")
+        } else {
+            ("#", "b")
+        };
+        match accidental {
+            a if a > 0 => sharp.repeat(a as usize),
+            a => flat.repeat(a.unsigned_abs() as usize),
+        }
+    }
+}
+
+// Letter and accidental of a key's tonic, as written when the key was
+// parsed and otherwise the conventional one
+pub fn tonic_spelling(key: Key) -> (usize, i8) {
+    if let Some(spelling) = key.spelling {
+        return spelling;
+    }
+    match key.mode {
+        Mode::Major => MAJOR_TONICS[(key.tonic % 12) as usize],
+        Mode::Minor => MINOR_TONICS[(key.tonic % 12) as usize],
+    }
+}
+
+// Tonic of a key as text in ASCII, e.g. Eb
+pub fn tonic_name(key: Key) -> String {
+    NoteNamer {
+        spelling: Spelling::Key(key),
+        ..NoteNamer::default()
+    }
+    .pitch_class(key.tonic)
+}
+
+// The seven scale notes of a key as (pitch class, letter, accidental)
+fn scale(key: Key) -> [(u8, usize, i8); 7] {
+    let intervals = match key.mode {
+        Mode::Major => MAJOR_SCALE,
+        Mode::Minor => MINOR_SCALE,
+    };
+    let (tonic_letter, _) = tonic_spelling(key);
+    let mut notes = [(0, 0, 0); 7];
+    for (degree, note) in notes.iter_mut().enumerate() {
+        let pitch_class = (key.tonic + intervals[degree]) % 12;
+        let letter = (tonic_letter + degree) % 7;
+        // Distance from the natural letter, wrapped into -6..=5
+        let accidental = (pitch_class as i8 - NATURALS[letter] as i8 + 18).rem_euclid(12) - 6;
+        *note = (pitch_class, letter, accidental);
+    }
+    notes
+}
+
+fn spell_in_key(pitch_class: u8, key: Key) -> (usize, i8) {
+    let scale = scale(key);
+    if let Some(&(_, letter, accidental)) = scale.iter().find(|n| n.0 == pitch_class) {
+        return (letter, accidental);
+    }
+    // A natural outside the scale keeps its plain letter (C in D major, B
+    // in F major, E in Db major)
+    if let Some(letter) = NATURALS.iter().position(|&n| n == pitch_class) {
+        return (letter, 0);
+    }
+    // Other chromatic notes lie a semitone from two scale notes; flat keys lower
+    // the one above, the others raise the one below, unless that takes a
+    // double accidental (G rather than F## in F# major)
+    let flat_key = scale.iter().any(|n| n.2 < 0);
+    let neighbour = |offset: u8| scale.iter().find(|n| n.0 == (pitch_class + offset) % 12);
+    let lowered = neighbour(1).map(|&(_, letter, accidental)| (letter, accidental - 1));
+    let raised = neighbour(11).map(|&(_, letter, accidental)| (letter, accidental + 1));
+    let (preferred, other) = if flat_key {
+        (lowered, raised)
+    } else {
+        (raised, lowered)
+    };
+    match (preferred, other) {
+        (Some(p), Some(o)) if p.1.abs() > 1 && o.1.abs() <= 1 => o,
+        (Some(p), _) => p,
+        (None, Some(o)) => o,
+        (None, None) => SHARPS[pitch_class as usize],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_key(key: &str) -> NoteNamer {
+        NoteNamer {
+            spelling: Spelling::Key(key.parse().unwrap()),
+            ..NoteNamer::default()
+        }
+    }
+
+    #[test]
+    fn key_spelling() {
+        let cases: &[(&str, u8, &str)] = &[
+            ("C", 61, "C#4"),
+            ("C", 70, "A#4"),
+            ("D", 60, "C4"),
+            ("D", 61, "C#4"),
+            ("D", 65, "F4"),
+            ("F", 70, "Bb4"),
+            ("F", 71, "B4"),
+            ("F", 66, "Gb4"),
+            ("Db", 64, "E4"),
+            ("Db", 60, "C4"),
+            ("Bbm", 64, "E4"),
+            ("Ebm", 64, "E4"),
+            ("Ebm", 71, "Cb5"),
+            ("F#", 65, "E#4"),
+            ("F#", 67, "G4"),
+            ("C#m", 72, "C5"),
+            ("Am", 68, "G#4"),
+            ("Gb", 71, "Cb5"),
+            ("Gb", 60, "C4"),
+            ("Gb", 66, "Gb4"),
+            ("C#", 61, "C#4"),
+            ("C#", 72, "B#4"),
+            ("Cb", 71, "Cb5"),
+            ("Cb", 64, "Fb4"),
+            ("D#m", 62, "D4"),
+            ("D#m", 63, "D#4"),
+        ];
+        for &(key, note, name) in cases {
+            assert_eq!(in_key(key).name(note), name, "{} in {}", note, key);
+        }
+    }
+
+    #[test]
+    fn octave_conventions() {
+        let namer = |octaves, spelling| NoteNamer {
+            octaves,
+            spelling,
+            ..NoteNamer::default()
+        };
+        let cases = [
+            (Octaves::Scientific, Spelling::Sharps, 60, "C4"),
+            (Octaves::Scientific, Spelling::Sharps, 21, "A0"),
+            (Octaves::Scientific, Spelling::Flats, 70, "Bb4"),
+            (Octaves::Scientific, Spelling::Sharps, 0, "C-1"),
+            (Octaves::MiddleC3, Spelling::Sharps, 60, "C3"),
+            (Octaves::MiddleC3, Spelling::Sharps, 69, "A3"),
+            (Octaves::Helmholtz, Spelling::Sharps, 60, "c'"),
+            (Octaves::Helmholtz, Spelling::Sharps, 72, "c''"),
+            (Octaves::Helmholtz, Spelling::Sharps, 59, "b"),
+            (Octaves::Helmholtz, Spelling::Sharps, 48, "c"),
+            (Octaves::Helmholtz, Spelling::Flats, 46, "Bb"),
+            (Octaves::Helmholtz, Spelling::Sharps, 36, "C"),
+            (Octaves::Helmholtz, Spelling::Sharps, 24, "C,"),
+        ];
+        for (octaves, spelling, note, name) in cases {
+            assert_eq!(namer(octaves, spelling).name(note), name);
+        }
+        // The octave follows the letter across the C boundary
+        let cb = namer(Octaves::Helmholtz, Spelling::Key("Gb".parse().unwrap()));
+        assert_eq!(cb.name(71), "cb''");
+        assert_eq!(in_key("C#").name(60), "B#3");
+    }
+
+    #[test]
+    fn key_names() {
+        let cases = [
+            ("Gb", "Gb major"),
+            ("F#", "F# major"),
+            ("C#", "C# major"),
+            ("Db", "Db major"),
+            ("Cb", "Cb major"),
+            ("ebm", "Eb minor"),
+            ("D#:minor", "D# minor"),
+        ];
+        for (key, name) in cases {
+            assert_eq!(key.parse::<Key>().unwrap().to_string(), name);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// Concert pitch: frequency of A4 in Hz
+pub const A4: f32 = 440.0;
+
+// Convert frequency to MIDI note number (A4 is 69), with A4 tuned to `a4` Hz
+pub fn freq_to_midi_note(freq: f32, a4: f32) -> Option<u8> {
+    if freq <= 0.0 {
+        return None;
+    }
+    let note_num = freq_to_midi(freq, a4);
+    if !(0.0..=127.0).contains(&note_num) {
+        None
+    } else {
+        Some(note_num.round() as u8)
+    }
+}
+
+// Fractional MIDI note number for a frequency (a4 -> 69.0)
+pub fn freq_to_midi(freq: f32, a4: f32) -> f32 {
+    69.0 + 12.0 * (freq / a4).log2()
+}
+
+// Frequency of a MIDI note number
+pub fn midi_to_freq(note: u8, a4: f32) -> f32 {
+    a4 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+// Deviation of a frequency from the given note in cents (100 per semitone)
+pub fn cents_from_note(freq: f32, note: u8, a4: f32) -> f32 {
+    (freq_to_midi(freq, a4) - note as f32) * 100.0
+}
+
+// Reference pitch moved by `cents` (100 per semitone)
+pub fn shift_reference(a4: f32, cents: f32) -> f32 {
+    a4 * 2f32.powf(cents / 1200.0)
+}
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+// Convert MIDI note number to note name (69 -> A4, 60 -> C4, etc.)
+pub fn midi_note_to_name(note: u8) -> String {
+    let octave = (note / 12).saturating_sub(1);
+    format!("{}{}", pitch_class_name(note), octave)
+}
+
+// Name of a pitch class without octave (0 -> C, 9 -> A); MIDI notes work too
+pub fn pitch_class_name(pitch_class: u8) -> &'static str {
+    NOTE_NAMES[(pitch_class % 12) as usize]
+}
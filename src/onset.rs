@@ -0,0 +1,229 @@
+use rustfft::num_complex::Complex;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+// Frames of onset strength before a candidate used for its adaptive threshold
+const PRE_FRAMES: usize = 8;
+// Frames after a candidate it has to wait for before it can be confirmed
+const POST_FRAMES: usize = 2;
+// Minimum number of frames between two onsets
+const MIN_GAP: usize = 2;
+// Height above the local mean, as a fraction of the recent maximum, an onset needs
+const DELTA: f32 = 0.1;
+// Per frame decay of the recent maximum
+const MAX_DECAY: f32 = 0.99;
+
+// Onset detection function computed from consecutive STFT frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnsetMethod {
+    // Rectified increase of log magnitude across bins
+    #[default]
+    SpectralFlux,
+    // Energy weighted by bin index, sensitive to percussive attacks
+    Hfc,
+    // Distance from the magnitude and phase predicted by the previous frames,
+    // catching soft onsets that barely change the magnitude
+    ComplexDomain,
+}
+
+impl FromStr for OnsetMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flux" | "spectral-flux" => Ok(OnsetMethod::SpectralFlux),
+            "hfc" => Ok(OnsetMethod::Hfc),
+            "complex" | "complex-domain" => Ok(OnsetMethod::ComplexDomain),
+            _ => Err(format!(
+                "invalid onset method '{}' (expected flux, hfc or complex)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OnsetMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OnsetMethod::SpectralFlux => "flux",
+            OnsetMethod::Hfc => "hfc",
+            OnsetMethod::ComplexDomain => "complex",
+        })
+    }
+}
+
+// A detected note onset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onset {
+    pub frame: usize,
+    // Centre of the onset frame in seconds
+    pub time: f32,
+    // Value of the detection function at the onset
+    pub strength: f32,
+}
+
+// The part of a frame's onset strength that depends on that frame alone,
+// so it can be computed for many frames at once in any order
+#[derive(Debug, Clone)]
+pub enum OnsetFeatures {
+    // ln(1 + magnitude) per bin, for the flux
+    LogMagnitudes(Vec<f32>),
+    // The finished value, for HFC
+    Value(f32),
+    // Bins with their magnitudes and phases, for complex domain
+    Polar(Vec<(Complex<f32>, f32, f32)>),
+}
+
+impl OnsetMethod {
+    // Per frame features of bins 0..fft_size / 2 of a spectrum
+    pub fn features(self, spectrum: &[Complex<f32>]) -> OnsetFeatures {
+        match self {
+            OnsetMethod::SpectralFlux => OnsetFeatures::LogMagnitudes(
+                spectrum.iter().map(|bin| (1.0 + bin.norm()).ln()).collect(),
+            ),
+            OnsetMethod::Hfc => OnsetFeatures::Value(
+                spectrum
+                    .iter()
+                    .enumerate()
+                    .map(|(k, bin)| k as f32 * bin.norm_sqr())
+                    .sum::<f32>()
+                    / spectrum.len().max(1) as f32,
+            ),
+            OnsetMethod::ComplexDomain => OnsetFeatures::Polar(
+                spectrum
+                    .iter()
+                    .map(|&bin| (bin, bin.norm(), bin.arg()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// Turns STFT frames into onset strength values, one per frame
+pub struct OnsetDetector {
+    method: OnsetMethod,
+    // State of the previous frames: log magnitudes for the flux,
+    // magnitudes and phases for complex domain
+    prev_log_mags: Vec<f32>,
+    prev_mags: Vec<f32>,
+    prev_phases: Vec<f32>,
+    prev_prev_phases: Vec<f32>,
+}
+
+impl OnsetDetector {
+    pub fn new(method: OnsetMethod) -> Self {
+        OnsetDetector {
+            method,
+            prev_log_mags: Vec::new(),
+            prev_mags: Vec::new(),
+            prev_phases: Vec::new(),
+            prev_prev_phases: Vec::new(),
+        }
+    }
+
+    // Onset strength of the next frame, given bins 0..fft_size / 2 of its spectrum
+    pub fn process(&mut self, spectrum: &[Complex<f32>]) -> f32 {
+        self.push(self.method.features(spectrum))
+    }
+
+    // Onset strength of the next frame from its features, frames in order
+    pub fn push(&mut self, features: OnsetFeatures) -> f32 {
+        match features {
+            OnsetFeatures::LogMagnitudes(log_mags) => {
+                if self.prev_log_mags.len() != log_mags.len() {
+                    // First frame: compare against silence
+                    self.prev_log_mags = vec![0.0; log_mags.len()];
+                }
+                let value = log_mags
+                    .iter()
+                    .zip(&self.prev_log_mags)
+                    .map(|(log_mag, prev)| (log_mag - prev).max(0.0))
+                    .sum();
+                self.prev_log_mags = log_mags;
+                value
+            }
+            OnsetFeatures::Value(value) => value,
+            OnsetFeatures::Polar(bins) => {
+                if self.prev_mags.len() != bins.len() {
+                    self.prev_mags = vec![0.0; bins.len()];
+                    self.prev_phases = vec![0.0; bins.len()];
+                    self.prev_prev_phases = vec![0.0; bins.len()];
+                }
+                let value = bins
+                    .iter()
+                    .enumerate()
+                    .map(|(k, (bin, _, _))| {
+                        // Previous magnitude, phase advanced at the previous rate
+                        let phase = 2.0 * self.prev_phases[k] - self.prev_prev_phases[k];
+                        let predicted = Complex::from_polar(self.prev_mags[k], wrap(phase));
+                        (bin - predicted).norm()
+                    })
+                    .sum();
+                std::mem::swap(&mut self.prev_prev_phases, &mut self.prev_phases);
+                for (k, &(_, mag, phase)) in bins.iter().enumerate() {
+                    self.prev_mags[k] = mag;
+                    self.prev_phases[k] = phase;
+                }
+                value
+            }
+        }
+    }
+}
+
+// Wrap a phase into (-pi, pi]
+fn wrap(phase: f32) -> f32 {
+    phase - 2.0 * PI * ((phase + PI) / (2.0 * PI)).floor()
+}
+
+// Adaptive peak picking over a stream of onset strength values. A frame is
+// an onset when it is the local maximum, stands above the local mean by a
+// fraction of the recent maximum, and is not too close to the last onset.
+#[derive(Default)]
+pub struct PeakPicker {
+    window: VecDeque<f32>,
+    pushed: usize,
+    last_onset: Option<usize>,
+    recent_max: f32,
+}
+
+impl PeakPicker {
+    pub fn new() -> Self {
+        PeakPicker::default()
+    }
+
+    // Feed the strength of the next frame. Returns the index and strength of
+    // a confirmed onset, which lags the newest frame by POST_FRAMES.
+    pub fn push(&mut self, value: f32) -> Option<(usize, f32)> {
+        self.window.push_back(value);
+        if self.window.len() > PRE_FRAMES + POST_FRAMES + 1 {
+            self.window.pop_front();
+        }
+        self.pushed += 1;
+        self.recent_max = value.max(self.recent_max * MAX_DECAY);
+
+        if self.pushed <= POST_FRAMES {
+            return None;
+        }
+        let frame = self.pushed - 1 - POST_FRAMES;
+        let at = self.window.len() - 1 - POST_FRAMES;
+        let candidate = self.window[at];
+
+        let is_max = self
+            .window
+            .iter()
+            .skip(at.saturating_sub(3))
+            .all(|&v| v <= candidate);
+        let mean = self.window.iter().sum::<f32>() / self.window.len() as f32;
+        let above = candidate > 0.0 && candidate >= mean + DELTA * self.recent_max;
+        let spaced = self.last_onset.is_none_or(|last| frame >= last + MIN_GAP);
+
+        if is_max && above && spaced {
+            self.last_onset = Some(frame);
+            Some((frame, candidate))
+        } else {
+            None
+        }
+    }
+}
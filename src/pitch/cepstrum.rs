@@ -0,0 +1,73 @@
+use super::{FrameInput, MAX_FREQ, Pitch, PitchDetector};
+use crate::interpolate::refine_extremum;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use std::sync::Arc;
+
+// Real cepstrum: the inverse FFT of the log spectrum turns the regular
+// spacing of harmonics into a peak at the period of the fundamental.
+pub struct Cepstrum {
+    ifft: Arc<dyn Fft<f32>>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Cepstrum {
+    pub fn new(fft_size: usize) -> Self {
+        let ifft = FftPlanner::new().plan_fft_inverse(fft_size);
+        let scratch = vec![Complex::new(0.0, 0.0); ifft.get_inplace_scratch_len()];
+        Cepstrum {
+            ifft,
+            buffer: vec![Complex::new(0.0, 0.0); fft_size],
+            scratch,
+        }
+    }
+}
+
+impl PitchDetector for Cepstrum {
+    fn name(&self) -> &'static str {
+        "cepstrum"
+    }
+
+    fn detect(&mut self, frame: &FrameInput) -> Option<Pitch> {
+        let size = self.buffer.len();
+        let half = size / 2;
+        if frame.spectrum.len() != half {
+            return None;
+        }
+
+        // Rebuild the full, mirrored log magnitude spectrum. The floor keeps
+        // near silent bins from drowning the harmonic ripple in log noise.
+        // Bin N/2 is not in the frame's spectrum and counts as silent, as
+        // does its mirror next to it for odd lengths.
+        let floor = frame.spectrum.iter().copied().fold(0.0, f32::max) * 1e-3 + 1e-9;
+        for (i, bin) in self.buffer.iter_mut().enumerate() {
+            let mirrored = i.min(size - i);
+            let mag = frame.spectrum.get(mirrored).copied().unwrap_or(0.0);
+            *bin = Complex::new((mag + floor).ln(), 0.0);
+        }
+        self.ifft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+
+        let q_min = ((frame.sample_rate / MAX_FREQ) as usize).max(2);
+        let q_max = ((frame.sample_rate / frame.min_freq) as usize).min(half);
+        if q_min + 2 >= q_max {
+            return None;
+        }
+        let cepstrum: Vec<f32> = self.buffer[..q_max + 1].iter().map(|c| c.re).collect();
+        // Skip the low quefrency lobe that describes the spectral envelope
+        let q_min = (q_min..q_max).find(|&q| cepstrum[q] < 0.0)?;
+        let q = (q_min..q_max).max_by(|&a, &b| cepstrum[a].total_cmp(&cepstrum[b]))?;
+        if cepstrum[q] <= 0.0 {
+            return None;
+        }
+
+        // Confidence grows with how far the peak stands out of the searched range
+        let mean =
+            cepstrum[q_min..q_max].iter().map(|c| c.abs()).sum::<f32>() / (q_max - q_min) as f32;
+        let (quefrency, _) = refine_extremum(&cepstrum, q);
+        Some(Pitch {
+            freq: frame.sample_rate / quefrency,
+            confidence: (1.0 - mean / cepstrum[q]).clamp(0.0, 1.0),
+        })
+    }
+}
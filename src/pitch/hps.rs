@@ -0,0 +1,82 @@
+use super::peak::energy_share;
+use super::{FrameInput, Pitch, PitchDetector};
+use crate::interpolate::{Interpolation, refine_peak};
+
+// Number of downsampled spectra multiplied together
+const HARMONICS: usize = 5;
+// Bins quieter than this fraction of the loudest can't be the fundamental
+const CANDIDATE_LEVEL: f32 = 0.1;
+
+// Harmonic product spectrum: multiplying the spectrum with copies of itself
+// compressed by 2, 3, ... lines the harmonics up on the fundamental.
+pub struct HarmonicProductSpectrum {
+    interpolation: Interpolation,
+}
+
+impl HarmonicProductSpectrum {
+    pub fn new(interpolation: Interpolation) -> Self {
+        HarmonicProductSpectrum { interpolation }
+    }
+}
+
+impl PitchDetector for HarmonicProductSpectrum {
+    fn name(&self) -> &'static str {
+        "hps"
+    }
+
+    fn detect(&mut self, frame: &FrameInput) -> Option<Pitch> {
+        let spectrum = frame.spectrum;
+        let bin_hz = frame.sample_rate / frame.fft_size as f32;
+        let min_bin = ((frame.min_freq / bin_hz).ceil() as usize).max(1);
+        let max_bin = spectrum.len() / HARMONICS;
+
+        // Sum logs instead of multiplying so quiet frames don't underflow. The
+        // floor stops a single missing harmonic from vetoing a candidate, and
+        // a candidate has to be a spectral peak carrying real energy to be a
+        // fundamental.
+        // Harmonics are looked up around multiples of the refined peak, since
+        // multiples of a whole bin drift off the higher partials.
+        let max = spectrum.iter().copied().fold(0.0, f32::max);
+        if max <= 0.0 {
+            return None;
+        }
+        let floor = max * 1e-2 + f32::EPSILON;
+        let mut best: Option<(f32, f32)> = None;
+        for bin in min_bin..max_bin {
+            if spectrum[bin] < max * CANDIDATE_LEVEL
+                || spectrum[bin] < spectrum[bin - 1]
+                || spectrum[bin] < spectrum[bin + 1]
+            {
+                continue;
+            }
+            let (peak_bin, _) = refine_peak(spectrum, bin, self.interpolation);
+            let product: f32 = harmonic_bins(peak_bin, spectrum.len())
+                .map(|center| (near_max(spectrum, center) + floor).ln())
+                .sum();
+            if best.is_none_or(|(_, b)| product > b) {
+                best = Some((peak_bin, product));
+            }
+        }
+        let (peak_bin, _) = best?;
+        let harmonics: Vec<usize> = harmonic_bins(peak_bin, spectrum.len()).collect();
+        Some(Pitch {
+            freq: frame.bin_to_freq(peak_bin),
+            confidence: energy_share(spectrum, &harmonics),
+        })
+    }
+}
+
+// Nearest bins to the first HARMONICS multiples of `f0_bin` in a spectrum of
+// `len` bins
+fn harmonic_bins(f0_bin: f32, len: usize) -> impl Iterator<Item = usize> {
+    (1..=HARMONICS)
+        .map(move |h| (f0_bin * h as f32).round() as usize)
+        .filter(move |&center| center < len)
+}
+
+// Strongest bin within one of `center`
+fn near_max(spectrum: &[f32], center: usize) -> f32 {
+    let lo = center.saturating_sub(1);
+    let hi = (center + 2).min(spectrum.len());
+    spectrum[lo..hi].iter().copied().fold(0.0, f32::max)
+}
@@ -0,0 +1,302 @@
+mod cepstrum;
+mod hps;
+mod mpm;
+mod peak;
+mod poly;
+mod yin;
+
+pub use cepstrum::Cepstrum;
+pub use hps::HarmonicProductSpectrum;
+pub use mpm::Mpm;
+pub use peak::Peak;
+pub use poly::{PolyPitch, Polyphonic, main_lobe_bins};
+pub use yin::Yin;
+
+use crate::interpolate::Interpolation;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Highest fundamental the lag based detectors search for (C8)
+const MAX_FREQ: f32 = 4186.0;
+
+// Everything a detector may look at for one analysis frame
+pub struct FrameInput<'a> {
+    // Raw, unwindowed samples of the frame
+    pub samples: &'a [f32],
+    // Magnitude spectrum of the windowed frame, bins 0..fft_size / 2
+    pub spectrum: &'a [f32],
+    pub sample_rate: f32,
+    // Transform length, which exceeds the frame when it was zero padded
+    pub fft_size: usize,
+    // Lowest fundamental worth reporting
+    pub min_freq: f32,
+}
+
+impl FrameInput<'_> {
+    pub fn bin_to_freq(&self, bin: f32) -> f32 {
+        bin * self.sample_rate / self.fft_size as f32
+    }
+
+    // Strongest spectral magnitude at the first few harmonics of `freq`,
+    // a loudness measure that still works when the fundamental is weak
+    pub fn harmonic_magnitude(&self, freq: f32) -> f32 {
+        let bin = freq * self.fft_size as f32 / self.sample_rate;
+        (1..=4)
+            .map(|h| (bin * h as f32).round() as usize)
+            .filter(|&center| center < self.spectrum.len())
+            .map(|center| {
+                let lo = center.saturating_sub(1);
+                let hi = (center + 2).min(self.spectrum.len());
+                self.spectrum[lo..hi].iter().copied().fold(0.0, f32::max)
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+// Fundamental frequency estimate for one frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pitch {
+    pub freq: f32,
+    // How much the detector trusts the estimate, 0 to 1
+    pub confidence: f32,
+}
+
+// A fundamental frequency estimator working frame by frame. Send so each
+// analysis thread can own one.
+pub trait PitchDetector: Send {
+    fn name(&self) -> &'static str;
+
+    // Estimate the fundamental of one frame, None when nothing periodic was found
+    fn detect(&mut self, frame: &FrameInput) -> Option<Pitch>;
+}
+
+// Built-in detectors, selectable by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DetectorKind {
+    // Loudest spectral bin, the original baseline
+    #[default]
+    Peak,
+    Yin,
+    Mpm,
+    Hps,
+    Cepstrum,
+}
+
+impl DetectorKind {
+    // Create a fresh detector for frames of `frame_size` samples transformed
+    // with `fft_size` points
+    pub fn build(
+        self,
+        frame_size: usize,
+        fft_size: usize,
+        interpolation: Interpolation,
+    ) -> Box<dyn PitchDetector> {
+        match self {
+            DetectorKind::Peak => Box::new(Peak::new(interpolation)),
+            DetectorKind::Yin => Box::new(Yin::new(frame_size)),
+            DetectorKind::Mpm => Box::new(Mpm::new(frame_size)),
+            DetectorKind::Hps => Box::new(HarmonicProductSpectrum::new(interpolation)),
+            DetectorKind::Cepstrum => Box::new(Cepstrum::new(fft_size)),
+        }
+    }
+}
+
+impl FromStr for DetectorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "peak" => Ok(DetectorKind::Peak),
+            "yin" => Ok(DetectorKind::Yin),
+            "mpm" | "mcleod" => Ok(DetectorKind::Mpm),
+            "hps" => Ok(DetectorKind::Hps),
+            "cepstrum" => Ok(DetectorKind::Cepstrum),
+            _ => Err(format!(
+                "invalid detector '{}' (expected peak, yin, mpm, hps or cepstrum)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DetectorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DetectorKind::Peak => "peak",
+            DetectorKind::Yin => "yin",
+            DetectorKind::Mpm => "mpm",
+            DetectorKind::Hps => "hps",
+            DetectorKind::Cepstrum => "cepstrum",
+        })
+    }
+}
+
+// Cross correlation via FFT, shared by the lag based detectors
+struct Correlator {
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    head: Vec<Complex<f32>>,
+    signal: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Correlator {
+    fn new(frame_size: usize) -> Self {
+        // Zero pad to at least twice the frame so the correlation is linear, not circular
+        let size = (2 * frame_size).next_power_of_two();
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(size);
+        let ifft = planner.plan_fft_inverse(size);
+        let scratch_len = fft
+            .get_inplace_scratch_len()
+            .max(ifft.get_inplace_scratch_len());
+        Correlator {
+            fft,
+            ifft,
+            head: vec![Complex::new(0.0, 0.0); size],
+            signal: vec![Complex::new(0.0, 0.0); size],
+            scratch: vec![Complex::new(0.0, 0.0); scratch_len],
+        }
+    }
+
+    // c(tau) = sum over j of head[j] * signal[j + tau], for tau in 0..lags
+    fn correlate(&mut self, head: &[f32], signal: &[f32], lags: usize, out: &mut Vec<f32>) {
+        let size = self.signal.len();
+        fill(&mut self.head, head);
+        fill(&mut self.signal, signal);
+        self.fft
+            .process_with_scratch(&mut self.head, &mut self.scratch);
+        self.fft
+            .process_with_scratch(&mut self.signal, &mut self.scratch);
+        for (s, h) in self.signal.iter_mut().zip(&self.head) {
+            *s *= h.conj();
+        }
+        self.ifft
+            .process_with_scratch(&mut self.signal, &mut self.scratch);
+        out.clear();
+        out.extend(self.signal[..lags].iter().map(|c| c.re / size as f32));
+    }
+}
+
+// Copy real samples into a zero padded complex buffer
+fn fill(buffer: &mut [Complex<f32>], samples: &[f32]) {
+    for (i, bin) in buffer.iter_mut().enumerate() {
+        *bin = Complex::new(samples.get(i).copied().unwrap_or(0.0), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft::RealFft;
+    use crate::window::WindowKind;
+    use rustfft::FftPlanner;
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+    const SIZE: usize = 4096;
+
+    // Detected pitch of a frame of `size` samples of harmonics 1..=partials
+    // of `f0`, the h-th at amplitude 1/h, zero padded to `fft_size`
+    fn detect(kind: DetectorKind, f0: f32, partials: usize, fft_size: usize) -> Option<Pitch> {
+        let samples: Vec<f32> = (0..SIZE)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                (1..=partials)
+                    .map(|h| (TAU * f0 * h as f32 * t).sin() / h as f32)
+                    .sum::<f32>()
+                    * 0.3
+            })
+            .collect();
+        let window = WindowKind::Hann.generate(SIZE);
+        let windowed: Vec<f32> = samples.iter().zip(&window).map(|(s, w)| s * w).collect();
+        let fft = RealFft::new(fft_size, &mut FftPlanner::new());
+        let mut spectrum = fft.make_output();
+        fft.process(&windowed, &mut spectrum, &mut fft.make_scratch());
+        let mags: Vec<f32> = spectrum[..fft_size / 2].iter().map(|c| c.norm()).collect();
+        let input = FrameInput {
+            samples: &samples,
+            spectrum: &mags,
+            sample_rate: SAMPLE_RATE,
+            fft_size,
+            min_freq: 50.0,
+        };
+        kind.build(SIZE, fft_size, Interpolation::Parabolic)
+            .detect(&input)
+    }
+
+    fn cents(freq: f32, f0: f32) -> f32 {
+        1200.0 * (freq / f0).log2()
+    }
+
+    const ALL: [DetectorKind; 5] = [
+        DetectorKind::Peak,
+        DetectorKind::Yin,
+        DetectorKind::Mpm,
+        DetectorKind::Hps,
+        DetectorKind::Cepstrum,
+    ];
+
+    #[test]
+    fn pure_tones() {
+        // The cepstrum needs harmonics to ripple the spectrum
+        for kind in &ALL[..4] {
+            for f0 in [110.0, 261.63, 440.0, 987.77] {
+                let pitch = detect(*kind, f0, 1, SIZE).unwrap();
+                let error = cents(pitch.freq, f0);
+                assert!(
+                    error.abs() < 10.0,
+                    "{} on {} Hz: {} Hz",
+                    kind,
+                    f0,
+                    pitch.freq
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn harmonic_tones() {
+        // The peak detector follows the loudest partial, which is the
+        // fundamental at 1/h amplitudes
+        for kind in ALL {
+            for f0 in [110.0, 220.0, 261.63, 440.0] {
+                let pitch = detect(kind, f0, 8, SIZE).unwrap();
+                let error = cents(pitch.freq, f0);
+                assert!(
+                    error.abs() < 20.0,
+                    "{} on {} Hz: {} Hz",
+                    kind,
+                    f0,
+                    pitch.freq
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn odd_and_padded_transforms() {
+        for kind in ALL {
+            for fft_size in [SIZE - 1, SIZE * 2] {
+                let pitch = detect(kind, 220.0, 8, fft_size).unwrap();
+                let error = cents(pitch.freq, 220.0);
+                assert!(
+                    error.abs() < 20.0,
+                    "{} with {} points: {} Hz",
+                    kind,
+                    fft_size,
+                    pitch.freq
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn silence() {
+        for kind in ALL {
+            assert!(detect(kind, 220.0, 0, SIZE).is_none(), "{}", kind);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use super::{Correlator, FrameInput, MAX_FREQ, Pitch, PitchDetector};
+use crate::interpolate::refine_extremum;
+
+// Key maxima within this fraction of the highest one are candidates
+const CUTOFF: f32 = 0.9;
+
+// McLeod pitch method (McLeod & Wyvill, 2005): picks the first strong key
+// maximum of the normalized square difference function.
+pub struct Mpm {
+    correlator: Correlator,
+    nsdf: Vec<f32>,
+}
+
+impl Mpm {
+    pub fn new(frame_size: usize) -> Self {
+        Mpm {
+            correlator: Correlator::new(frame_size),
+            nsdf: Vec::new(),
+        }
+    }
+}
+
+impl PitchDetector for Mpm {
+    fn name(&self) -> &'static str {
+        "mpm"
+    }
+
+    fn detect(&mut self, frame: &FrameInput) -> Option<Pitch> {
+        let samples = frame.samples;
+        let n = samples.len();
+        let tau_min = ((frame.sample_rate / MAX_FREQ) as usize).max(2);
+        let tau_max = ((frame.sample_rate / frame.min_freq) as usize).min(n / 2);
+        if tau_min + 2 >= tau_max {
+            return None;
+        }
+
+        // n(tau) = 2 r(tau) / m(tau), where m(tau) sums the squares of both overlapping parts
+        self.correlator
+            .correlate(samples, samples, tau_max, &mut self.nsdf);
+        let mut m = 2.0 * samples.iter().map(|x| x * x).sum::<f32>();
+        for tau in 0..tau_max {
+            self.nsdf[tau] = if m > 0.0 {
+                2.0 * self.nsdf[tau] / m
+            } else {
+                0.0
+            };
+            m -= samples[n - 1 - tau].powi(2) + samples[tau].powi(2);
+        }
+
+        // Highest point of every positive lobe after the first zero crossing
+        let mut maxima = Vec::new();
+        let mut tau = (1..tau_max).find(|&t| self.nsdf[t] <= 0.0)?;
+        while tau < tau_max {
+            while tau < tau_max && self.nsdf[tau] <= 0.0 {
+                tau += 1;
+            }
+            let mut best: Option<usize> = None;
+            while tau < tau_max && self.nsdf[tau] > 0.0 {
+                if best.is_none_or(|b| self.nsdf[tau] > self.nsdf[b]) {
+                    best = Some(tau);
+                }
+                tau += 1;
+            }
+            maxima.extend(best.filter(|&b| b >= tau_min));
+        }
+
+        let highest = maxima.iter().map(|&t| self.nsdf[t]).fold(0.0, f32::max);
+        if highest <= 0.0 {
+            return None;
+        }
+        let tau = *maxima.iter().find(|&&t| self.nsdf[t] >= CUTOFF * highest)?;
+        let (period, clarity) = refine_extremum(&self.nsdf, tau);
+        Some(Pitch {
+            freq: frame.sample_rate / period,
+            confidence: clarity.clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,61 @@
+use super::{FrameInput, Pitch, PitchDetector};
+use crate::interpolate::{Interpolation, refine_peak};
+
+// Loudest bin of the magnitude spectrum. Fast, but locks onto whichever
+// harmonic is strongest, so it makes octave errors on rich timbres.
+pub struct Peak {
+    interpolation: Interpolation,
+}
+
+impl Peak {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Peak { interpolation }
+    }
+}
+
+impl PitchDetector for Peak {
+    fn name(&self) -> &'static str {
+        "peak"
+    }
+
+    fn detect(&mut self, frame: &FrameInput) -> Option<Pitch> {
+        let spectrum = frame.spectrum;
+        let mut max_mag = 0.0;
+        let mut max_bin = 0;
+        for (i, &mag) in spectrum.iter().enumerate().skip(1) {
+            if mag > max_mag {
+                max_mag = mag;
+                max_bin = i;
+            }
+        }
+        if max_bin == 0 {
+            return None;
+        }
+
+        // Refine the peak between bins for sub-bin frequency accuracy
+        let (peak_bin, _) = refine_peak(spectrum, max_bin, self.interpolation);
+        Some(Pitch {
+            freq: frame.bin_to_freq(peak_bin),
+            confidence: energy_share(spectrum, &[max_bin]),
+        })
+    }
+}
+
+// Fraction of the spectral energy found within one bin of the given peaks
+pub(super) fn energy_share(spectrum: &[f32], peaks: &[usize]) -> f32 {
+    let total: f32 = spectrum.iter().map(|m| m * m).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let mut counted = vec![false; spectrum.len()];
+    let mut energy = 0.0;
+    for &peak in peaks {
+        for bin in peak.saturating_sub(1)..(peak + 2).min(spectrum.len()) {
+            if !counted[bin] {
+                counted[bin] = true;
+                energy += spectrum[bin] * spectrum[bin];
+            }
+        }
+    }
+    (energy / total).min(1.0)
+}
@@ -0,0 +1,146 @@
+use super::{FrameInput, Pitch};
+use crate::interpolate::{Interpolation, refine_peak};
+use crate::window::WindowKind;
+
+// Partials summed into a candidate's salience
+const HARMONICS: usize = 8;
+// Highest fundamental considered (C7)
+const MAX_FREQ: f32 = 2093.0;
+// Voices weaker than this fraction of the first one are treated as residue
+const SALIENCE_RATIO: f32 = 0.2;
+// A candidate's own peak must reach this fraction of the loudest residual bin
+const CANDIDATE_LEVEL: f32 = 0.05;
+
+// Main lobe half width of `window` in bins of an FFT padded `zero_padding`
+// times, rounded up so the whole lobe is cleared
+pub fn main_lobe_bins(window: WindowKind, zero_padding: usize) -> usize {
+    (window.main_lobe() * zero_padding.max(1) as f32).ceil() as usize
+}
+
+// One fundamental found in a polyphonic frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolyPitch {
+    pub pitch: Pitch,
+    // Interpolated magnitude of the fundamental's spectral peak
+    pub magnitude: f32,
+}
+
+// Multiple fundamental estimation by iterative harmonic subtraction: pick the
+// candidate whose harmonic series carries the most energy, remove its
+// partials from the spectrum, and repeat on the residual.
+pub struct Polyphonic {
+    max_voices: usize,
+    interpolation: Interpolation,
+    // Half width in bins of the window's main lobe, cleared around each
+    // subtracted partial
+    lobe: usize,
+    residual: Vec<f32>,
+}
+
+impl Polyphonic {
+    // `lobe` is the main lobe half width in bins of the padded transform,
+    // see `main_lobe_bins`
+    pub fn new(max_voices: usize, interpolation: Interpolation, lobe: usize) -> Self {
+        Polyphonic {
+            max_voices,
+            interpolation,
+            lobe: lobe.max(1),
+            residual: Vec::new(),
+        }
+    }
+
+    // Fundamentals found in the frame, most salient first
+    pub fn detect(&mut self, frame: &FrameInput) -> Vec<PolyPitch> {
+        let bin_hz = frame.sample_rate / frame.fft_size as f32;
+        let min_bin = ((frame.min_freq / bin_hz).ceil() as usize).max(1);
+        let max_bin = ((MAX_FREQ / bin_hz) as usize).min(frame.spectrum.len().saturating_sub(1));
+        self.residual.clear();
+        self.residual.extend_from_slice(frame.spectrum);
+
+        let mut voices = Vec::new();
+        let mut first_salience = None;
+        while voices.len() < self.max_voices {
+            let Some((bin, salience)) = self.best_candidate(min_bin, max_bin) else {
+                break;
+            };
+            let first = *first_salience.get_or_insert(salience);
+            if salience < first * SALIENCE_RATIO {
+                break;
+            }
+
+            let (peak, magnitude) = refine_peak(&self.residual, bin, self.interpolation);
+            voices.push(PolyPitch {
+                pitch: Pitch {
+                    freq: frame.bin_to_freq(peak),
+                    confidence: (salience / first).min(1.0),
+                },
+                magnitude,
+            });
+            self.subtract(peak);
+        }
+        voices
+    }
+
+    // Strongest local peak by weighted harmonic sum
+    fn best_candidate(&self, min_bin: usize, max_bin: usize) -> Option<(usize, f32)> {
+        let spectrum = &self.residual;
+        let loudest = spectrum.iter().copied().fold(0.0, f32::max);
+        if loudest <= 0.0 {
+            return None;
+        }
+        let mut best: Option<(usize, f32)> = None;
+        for bin in min_bin.max(1)..max_bin {
+            let mag = spectrum[bin];
+            if mag < loudest * CANDIDATE_LEVEL || mag < spectrum[bin - 1] || mag < spectrum[bin + 1]
+            {
+                continue;
+            }
+            let (peak, _) = refine_peak(spectrum, bin, self.interpolation);
+            let salience = self.salience(peak);
+            if best.is_none_or(|(_, s)| salience > s) {
+                best = Some((bin, salience));
+            }
+        }
+        best
+    }
+
+    // Sum of the strongest bin near each harmonic, weighted down as 1/h so a
+    // sub-octave can't win on the even partials of the true fundamental
+    fn salience(&self, f0_bin: f32) -> f32 {
+        (1..=HARMONICS)
+            .filter_map(|h| {
+                let center = (f0_bin * h as f32).round() as usize;
+                let lo = center.saturating_sub(1);
+                let hi = (center + 2).min(self.residual.len());
+                (lo < hi)
+                    .then(|| self.residual[lo..hi].iter().copied().fold(0.0, f32::max) / h as f32)
+            })
+            .sum()
+    }
+
+    // Clear the main lobe around every partial of the found voice
+    fn subtract(&mut self, f0_bin: f32) {
+        for h in 1..=HARMONICS {
+            let center = (f0_bin * h as f32).round() as usize;
+            let lo = center.saturating_sub(self.lobe);
+            let hi = (center + self.lobe + 1).min(self.residual.len());
+            if lo < hi {
+                self.residual[lo..hi].iter_mut().for_each(|m| *m = 0.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lobe_scales_with_window_and_padding() {
+        assert_eq!(main_lobe_bins(WindowKind::Hann, 1), 2);
+        assert_eq!(main_lobe_bins(WindowKind::Hann, 4), 8);
+        assert_eq!(main_lobe_bins(WindowKind::BlackmanHarris, 1), 4);
+        assert_eq!(main_lobe_bins(WindowKind::BlackmanHarris, 2), 8);
+        assert_eq!(main_lobe_bins(WindowKind::Kaiser { beta: 8.6 }, 1), 3);
+    }
+}
@@ -0,0 +1,90 @@
+use super::{Correlator, FrameInput, MAX_FREQ, Pitch, PitchDetector};
+use crate::interpolate::refine_extremum;
+
+// Dips of the normalized difference below this count as periodic
+const THRESHOLD: f32 = 0.15;
+
+// YIN (de Cheveigné & Kawahara, 2002): finds the first lag at which the
+// cumulative mean normalized difference function dips below a threshold.
+pub struct Yin {
+    correlator: Correlator,
+    correlation: Vec<f32>,
+    diff: Vec<f32>,
+}
+
+impl Yin {
+    pub fn new(frame_size: usize) -> Self {
+        Yin {
+            correlator: Correlator::new(frame_size),
+            correlation: Vec::new(),
+            diff: Vec::new(),
+        }
+    }
+}
+
+impl PitchDetector for Yin {
+    fn name(&self) -> &'static str {
+        "yin"
+    }
+
+    fn detect(&mut self, frame: &FrameInput) -> Option<Pitch> {
+        let samples = frame.samples;
+        // Integration window is half the frame, leaving the other half for lags
+        let window = samples.len() / 2;
+        let tau_min = ((frame.sample_rate / MAX_FREQ) as usize).max(2);
+        let tau_max = ((frame.sample_rate / frame.min_freq) as usize).min(window);
+        if tau_min + 2 >= tau_max {
+            return None;
+        }
+
+        // A silent window has no period to find
+        let head_energy: f32 = samples[..window].iter().map(|x| x * x).sum();
+        if head_energy == 0.0 {
+            return None;
+        }
+
+        // d(tau) = sum of (x[j] - x[j + tau])^2 over the window
+        self.correlator
+            .correlate(&samples[..window], samples, tau_max, &mut self.correlation);
+        let mut lag_energy = head_energy;
+        self.diff.clear();
+        for tau in 0..tau_max {
+            self.diff
+                .push((head_energy + lag_energy - 2.0 * self.correlation[tau]).max(0.0));
+            lag_energy += samples[window + tau].powi(2) - samples[tau].powi(2);
+        }
+
+        // Cumulative mean normalization
+        let mut running = 0.0;
+        self.diff[0] = 1.0;
+        for tau in 1..tau_max {
+            running += self.diff[tau];
+            self.diff[tau] = if running > 0.0 {
+                self.diff[tau] * tau as f32 / running
+            } else {
+                1.0
+            };
+        }
+
+        // First dip under the threshold, followed down to its local minimum;
+        // without one, fall back to the global minimum
+        let tau = match (tau_min..tau_max).find(|&t| self.diff[t] < THRESHOLD) {
+            Some(mut tau) => {
+                while tau + 1 < tau_max && self.diff[tau + 1] < self.diff[tau] {
+                    tau += 1;
+                }
+                tau
+            }
+            None => (tau_min..tau_max).min_by(|&a, &b| self.diff[a].total_cmp(&self.diff[b]))?,
+        };
+
+        let (period, dip) = refine_extremum(&self.diff, tau);
+        if period <= 0.0 {
+            return None;
+        }
+        Some(Pitch {
+            freq: frame.sample_rate / period,
+            confidence: (1.0 - dip).clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,369 @@
+use crate::analysis::{Analysis, AnalyzerConfig, Frame};
+use crate::channels::ChannelMode;
+use crate::chords::ChordSegment;
+use crate::chroma::Chromagram;
+use crate::json::Json;
+use crate::key::{KeyProfile, KeyScore};
+use crate::naming::tonic_name;
+use crate::notes::{midi_note_to_name, pitch_class_name};
+use crate::segment::NoteEvent;
+use crate::tuning::TuningEstimate;
+use hound::{SampleFormat, WavSpec};
+
+// Bumped whenever a field is renamed or removed; adding fields keeps the version
+pub const SCHEMA_VERSION: u32 = 1;
+
+// Results for one analyzed signal of a file
+pub struct SignalReport<'a> {
+    pub label: &'a str,
+    pub analysis: &'a Analysis,
+    pub events: &'a [NoteEvent],
+    pub chroma: Option<&'a Chromagram>,
+    pub chords: &'a [ChordSegment],
+}
+
+// Full machine readable report for one file
+pub fn json_report(
+    path: &str,
+    spec: &WavSpec,
+    duration: f32,
+    mode: ChannelMode,
+    config: &AnalyzerConfig,
+    signals: &[SignalReport],
+) -> Json {
+    Json::object()
+        .field("schema", "wav_note_detector.analysis")
+        .field("version", SCHEMA_VERSION)
+        .field("file", file_json(path, spec, duration))
+        .field("parameters", parameters_json(mode, config))
+        .field(
+            "signals",
+            signals.iter().map(signal_json).collect::<Vec<_>>(),
+        )
+}
+
+// Combined report for several files with an aggregate note histogram
+pub fn batch_report(
+    files: Vec<Json>,
+    aggregate: &[(u8, usize)],
+    failures: &[(String, String)],
+) -> Json {
+    Json::object()
+        .field("schema", "wav_note_detector.batch")
+        .field("version", SCHEMA_VERSION)
+        .field("files", files)
+        .field(
+            "aggregate",
+            aggregate
+                .iter()
+                .map(|&(note, count)| {
+                    Json::object()
+                        .field("note", note)
+                        .field("name", midi_note_to_name(note))
+                        .field("count", count)
+                })
+                .collect::<Vec<_>>(),
+        )
+        .field(
+            "failures",
+            failures
+                .iter()
+                .map(|(path, error)| {
+                    Json::object()
+                        .field("path", path.as_str())
+                        .field("error", error.as_str())
+                })
+                .collect::<Vec<_>>(),
+        )
+}
+
+pub fn file_json(path: &str, spec: &WavSpec, duration: f32) -> Json {
+    Json::object()
+        .field("path", path)
+        .field("sample_rate", spec.sample_rate)
+        .field("channels", spec.channels)
+        .field("bits_per_sample", spec.bits_per_sample)
+        .field(
+            "sample_format",
+            match spec.sample_format {
+                SampleFormat::Int => "int",
+                SampleFormat::Float => "float",
+            },
+        )
+        .field("duration", duration)
+}
+
+pub fn parameters_json(mode: ChannelMode, config: &AnalyzerConfig) -> Json {
+    Json::object()
+        .field("channel_mode", mode.to_string())
+        .field("fft_size", config.fft_size)
+        .field("hop_size", config.hop_size)
+        .field("zero_padding", config.zero_padding)
+        .field("window", config.window.to_string())
+        .field("interpolation", config.interpolation.to_string())
+        .field("detector", config.detector.to_string())
+        .field("polyphony", config.polyphony)
+        .field("onset_method", config.onset_method.to_string())
+        .field("reference", config.reference)
+        .field("min_freq", config.min_freq)
+        .field("min_magnitude", config.min_magnitude)
+}
+
+pub fn signal_json(signal: &SignalReport) -> Json {
+    let analysis = signal.analysis;
+    Json::object()
+        .field("label", signal.label)
+        .field("histogram", histogram_json(analysis))
+        .field(
+            "frames",
+            analysis.frames.iter().map(frame_json).collect::<Vec<_>>(),
+        )
+        .field(
+            "onsets",
+            analysis
+                .onsets
+                .iter()
+                .map(|o| {
+                    Json::object()
+                        .field("frame", o.frame)
+                        .field("time", o.time)
+                        .field("strength", o.strength)
+                })
+                .collect::<Vec<_>>(),
+        )
+        .field(
+            "events",
+            signal.events.iter().map(event_json).collect::<Vec<_>>(),
+        )
+}
+
+// Per-frame pitch tracks of several files
+pub fn track_report(files: &[(&str, Vec<SignalReport>)]) -> Json {
+    let files: Vec<Json> = files
+        .iter()
+        .map(|(path, signals)| {
+            let signals: Vec<Json> = signals
+                .iter()
+                .map(|signal| {
+                    let frames: Vec<Json> = signal.analysis.frames.iter().map(frame_json).collect();
+                    Json::object()
+                        .field("label", signal.label)
+                        .field("frames", frames)
+                })
+                .collect();
+            Json::object()
+                .field("path", *path)
+                .field("signals", signals)
+        })
+        .collect();
+    Json::object()
+        .field("schema", "wav_note_detector.track")
+        .field("version", SCHEMA_VERSION)
+        .field("files", files)
+}
+
+// Note events of several files
+pub fn transcription_report(files: &[(&str, Vec<SignalReport>)]) -> Json {
+    let files: Vec<Json> = files
+        .iter()
+        .map(|(path, signals)| {
+            let signals: Vec<Json> = signals
+                .iter()
+                .map(|signal| {
+                    let events: Vec<Json> = signal.events.iter().map(event_json).collect();
+                    Json::object()
+                        .field("label", signal.label)
+                        .field("events", events)
+                })
+                .collect();
+            Json::object()
+                .field("path", *path)
+                .field("signals", signals)
+        })
+        .collect();
+    Json::object()
+        .field("schema", "wav_note_detector.transcription")
+        .field("version", SCHEMA_VERSION)
+        .field("files", files)
+}
+
+// Chromagrams of several files; signals without one are left out
+pub fn chroma_report(files: &[(&str, Vec<SignalReport>)]) -> Json {
+    let files: Vec<Json> = files
+        .iter()
+        .map(|(path, signals)| {
+            let signals: Vec<Json> = signals
+                .iter()
+                .filter_map(|signal| {
+                    let chroma = signal.chroma?;
+                    let frames: Vec<Json> = (0..chroma.frames.len())
+                        .map(|i| {
+                            Json::object()
+                                .field("time", chroma.time(i))
+                                .field("chroma", chroma.frames[i].to_vec())
+                        })
+                        .collect();
+                    Some(
+                        Json::object()
+                            .field("label", signal.label)
+                            .field("frames", frames),
+                    )
+                })
+                .collect();
+            Json::object()
+                .field("path", *path)
+                .field("signals", signals)
+        })
+        .collect();
+    let classes: Vec<&str> = (0..12).map(pitch_class_name).collect();
+    Json::object()
+        .field("schema", "wav_note_detector.chroma")
+        .field("version", SCHEMA_VERSION)
+        .field("pitch_classes", classes)
+        .field("files", files)
+}
+
+// Chord timelines of several files
+pub fn chords_report(files: &[(&str, Vec<SignalReport>)]) -> Json {
+    let files: Vec<Json> = files
+        .iter()
+        .map(|(path, signals)| {
+            let signals: Vec<Json> = signals
+                .iter()
+                .map(|signal| {
+                    let chords: Vec<Json> = signal.chords.iter().map(chord_json).collect();
+                    Json::object()
+                        .field("label", signal.label)
+                        .field("chords", chords)
+                })
+                .collect();
+            Json::object()
+                .field("path", *path)
+                .field("signals", signals)
+        })
+        .collect();
+    Json::object()
+        .field("schema", "wav_note_detector.chords")
+        .field("version", SCHEMA_VERSION)
+        .field("files", files)
+}
+
+// Root and quality are null for no chord
+pub fn chord_json(segment: &ChordSegment) -> Json {
+    Json::object()
+        .field("chord", segment.label())
+        .field("root", segment.chord.map(|c| pitch_class_name(c.root)))
+        .field("quality", segment.chord.map(|c| c.quality.to_string()))
+        .field("start", segment.start)
+        .field("end", segment.end)
+}
+
+// Tuning offsets of several files; `estimate` is None when a file has no notes
+pub fn tuning_report(files: &[(&str, f32, Option<&TuningEstimate>)], reference: f32) -> Json {
+    let files: Vec<Json> = files
+        .iter()
+        .map(|&(path, reference, estimate)| {
+            Json::object()
+                .field("path", path)
+                .field("tuning", estimate.map(|e| tuning_json(e, reference)))
+        })
+        .collect();
+    Json::object()
+        .field("schema", "wav_note_detector.tuning")
+        .field("version", SCHEMA_VERSION)
+        .field("reference", reference)
+        .field("files", files)
+}
+
+// Histogram bins are one cent wide, the first starting at -50 cents
+pub fn tuning_json(estimate: &TuningEstimate, reference: f32) -> Json {
+    Json::object()
+        .field("offset_cents", estimate.offset)
+        .field("estimated_reference", estimate.reference(reference))
+        .field("confidence", estimate.confidence)
+        .field("count", estimate.count)
+        .field("histogram", estimate.histogram.clone())
+}
+
+// Key estimates of several files from their pitch class profiles
+pub fn key_report(files: &[(&str, [f32; 12], Vec<KeyScore>)], profile: KeyProfile) -> Json {
+    let files: Vec<Json> = files
+        .iter()
+        .map(|(path, pitch_classes, scores)| {
+            let keys: Vec<Json> = scores.iter().map(key_json).collect();
+            Json::object()
+                .field("path", *path)
+                .field("pitch_classes", pitch_classes.to_vec())
+                .field("keys", keys)
+        })
+        .collect();
+    Json::object()
+        .field("schema", "wav_note_detector.key")
+        .field("version", SCHEMA_VERSION)
+        .field("profile", profile.to_string())
+        .field("files", files)
+}
+
+pub fn key_json(score: &KeyScore) -> Json {
+    Json::object()
+        .field("key", score.key.to_string())
+        .field("tonic", tonic_name(score.key))
+        .field("mode", score.key.mode.to_string())
+        .field("correlation", score.correlation)
+}
+
+// Note histogram, most common first
+pub fn histogram_json(analysis: &Analysis) -> Json {
+    analysis
+        .note_counts
+        .iter()
+        .map(|&(note, count)| {
+            let (freq, cents) = analysis.note_pitch(note).unwrap_or_default();
+            Json::object()
+                .field("note", note)
+                .field("name", midi_note_to_name(note))
+                .field("count", count)
+                .field("mean_freq", freq)
+                .field("mean_cents", cents)
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+pub fn frame_json(frame: &Frame) -> Json {
+    Json::object()
+        .field("index", frame.index)
+        .field("time", frame.time)
+        .field("voiced", frame.note.is_some())
+        .field("freq", frame.freq)
+        .field("note", frame.note)
+        .field("name", frame.note.map(midi_note_to_name))
+        .field("cents", frame.cents)
+        .field("magnitude", frame.magnitude)
+        .field("confidence", frame.confidence)
+        .field(
+            "voices",
+            frame
+                .voices
+                .iter()
+                .map(|v| {
+                    Json::object()
+                        .field("note", v.note)
+                        .field("name", midi_note_to_name(v.note))
+                        .field("freq", v.freq)
+                        .field("cents", v.cents)
+                        .field("magnitude", v.magnitude)
+                })
+                .collect::<Vec<_>>(),
+        )
+}
+
+pub fn event_json(event: &NoteEvent) -> Json {
+    Json::object()
+        .field("note", event.note)
+        .field("name", midi_note_to_name(event.note))
+        .field("start", event.start)
+        .field("end", event.end)
+        .field("duration", event.duration())
+        .field("mean_amplitude", event.mean_amplitude)
+}
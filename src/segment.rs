@@ -0,0 +1,175 @@
+use crate::analysis::Analysis;
+use std::collections::{BTreeMap, BTreeSet};
+
+// At an onset, a held note restarts if its magnitude grew by this factor
+const REATTACK: f32 = 1.1;
+
+// A note held over consecutive frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteEvent {
+    pub note: u8,
+    // Onset and release in seconds
+    pub start: f32,
+    pub end: f32,
+    // Mean spectral magnitude of the note over its frames
+    pub mean_amplitude: f32,
+}
+
+impl NoteEvent {
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+}
+
+// Note being held while frames are scanned
+struct OpenEvent {
+    start: f32,
+    amplitude: f32,
+    frames: usize,
+    last_magnitude: f32,
+}
+
+impl OpenEvent {
+    fn close(self, note: u8, end: f32) -> NoteEvent {
+        NoteEvent {
+            note,
+            start: self.start,
+            end,
+            mean_amplitude: self.amplitude / self.frames as f32,
+        }
+    }
+}
+
+// Merge consecutive frames sounding the same MIDI note into note events.
+// Every voice is tracked separately, so chords produce overlapping events.
+// Onsets split a held note when it is struck again, so repeated notes of
+// the same pitch come out as separate events.
+pub fn segment(analysis: &Analysis) -> Vec<NoteEvent> {
+    let hop_time = analysis.hop_size as f32 / analysis.sample_rate;
+    let onsets: BTreeSet<usize> = analysis.onsets.iter().map(|o| o.frame).collect();
+    let mut open: BTreeMap<u8, OpenEvent> = BTreeMap::new();
+    let mut events = Vec::new();
+
+    for frame in &analysis.frames {
+        let is_onset = onsets.contains(&frame.index);
+        // Close notes that stopped sounding, or were struck again, in this frame
+        let stopped: Vec<u8> = open
+            .iter()
+            .filter(|(note, event)| {
+                frame
+                    .voices
+                    .iter()
+                    .find(|v| v.note == **note)
+                    .is_none_or(|v| is_onset && v.magnitude > event.last_magnitude * REATTACK)
+            })
+            .map(|(note, _)| *note)
+            .collect();
+        for note in stopped {
+            if let Some(event) = open.remove(&note) {
+                events.push(event.close(note, frame.time));
+            }
+        }
+
+        for voice in &frame.voices {
+            let event = open.entry(voice.note).or_insert(OpenEvent {
+                start: frame.time,
+                amplitude: 0.0,
+                frames: 0,
+                last_magnitude: 0.0,
+            });
+            event.amplitude += voice.magnitude;
+            event.frames += 1;
+            event.last_magnitude = voice.magnitude;
+        }
+    }
+
+    // Notes still sounding end with the last frame
+    let end = analysis.frames.last().map_or(0.0, |f| f.time + hop_time);
+    events.extend(open.into_iter().map(|(note, event)| event.close(note, end)));
+
+    events.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.note.cmp(&b.note)));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Frame, Voice};
+    use crate::onset::Onset;
+
+    // 100 frames per second, each sounding the given (note, magnitude) voices
+    fn analysis(frames: &[&[(u8, f32)]], onsets: &[usize]) -> Analysis {
+        let frames = frames
+            .iter()
+            .enumerate()
+            .map(|(index, voices)| {
+                let voices: Vec<Voice> = voices
+                    .iter()
+                    .map(|&(note, magnitude)| Voice {
+                        note,
+                        freq: 440.0,
+                        cents: 0.0,
+                        magnitude,
+                    })
+                    .collect();
+                Frame {
+                    index,
+                    time: index as f32 / 100.0,
+                    freq: 440.0,
+                    magnitude: 1.0,
+                    confidence: 1.0,
+                    note: voices.first().map(|v| v.note),
+                    cents: 0.0,
+                    voices,
+                    onset_strength: 0.0,
+                }
+            })
+            .collect();
+        Analysis {
+            sample_rate: 100.0,
+            hop_size: 1,
+            frames,
+            onsets: onsets
+                .iter()
+                .map(|&frame| Onset {
+                    frame,
+                    time: frame as f32 / 100.0,
+                    strength: 1.0,
+                })
+                .collect(),
+            note_counts: Vec::new(),
+        }
+    }
+
+    fn spans(events: &[NoteEvent]) -> Vec<(u8, f32, f32)> {
+        events
+            .iter()
+            .map(|e| (e.note, (e.start * 100.0).round(), (e.end * 100.0).round()))
+            .collect()
+    }
+
+    #[test]
+    fn repeated_note_splits_at_louder_onset() {
+        let a: &[(u8, f32)] = &[(69, 1.0)];
+        let struck: &[(u8, f32)] = &[(69, 2.0)];
+        let events = segment(&analysis(&[a, a, a, struck, a, a], &[0, 3]));
+        assert_eq!(spans(&events), [(69, 0.0, 3.0), (69, 3.0, 6.0)]);
+    }
+
+    #[test]
+    fn held_note_survives_quieter_onset() {
+        // An onset from another note doesn't cut a held note that didn't grow
+        let a: &[(u8, f32)] = &[(69, 1.0)];
+        let both: &[(u8, f32)] = &[(69, 0.9), (72, 1.0)];
+        let events = segment(&analysis(&[a, a, both, both], &[0, 2]));
+        assert_eq!(spans(&events), [(69, 0.0, 4.0), (72, 2.0, 4.0)]);
+    }
+
+    #[test]
+    fn repeated_note_after_gap() {
+        let a: &[(u8, f32)] = &[(69, 1.0)];
+        let events = segment(&analysis(&[a, a, &[], a], &[]));
+        assert_eq!(spans(&events), [(69, 0.0, 2.0), (69, 3.0, 4.0)]);
+        assert_eq!(events[0].mean_amplitude, 1.0);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::analysis::Analysis;
+use crate::chroma::Chromagram;
+use crate::notes::{midi_note_to_name, pitch_class_name};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Field separator of a delimited text table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+impl Delimiter {
+    fn as_char(self) -> char {
+        match self {
+            Delimiter::Comma => ',',
+            Delimiter::Tab => '\t',
+        }
+    }
+}
+
+const HEADER: [&str; 10] = [
+    "signal",
+    "frame",
+    "time",
+    "voiced",
+    "freq",
+    "midi_note",
+    "note_name",
+    "cents",
+    "magnitude",
+    "confidence",
+];
+
+// Write the per-frame pitch track of every signal to a CSV or TSV file
+pub fn write_pitch_track_file<P: AsRef<Path>>(
+    path: P,
+    signals: &[(&str, &Analysis)],
+    delimiter: Delimiter,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_pitch_track(&mut out, signals, delimiter)?;
+    out.flush()
+}
+
+// One row per FFT frame, including frames rejected as noise, which are
+// marked unvoiced and leave the note columns empty
+pub fn write_pitch_track<W: Write>(
+    out: &mut W,
+    signals: &[(&str, &Analysis)],
+    delimiter: Delimiter,
+) -> io::Result<()> {
+    write_row(out, delimiter, HEADER.iter().map(|h| h.to_string()))?;
+    for (label, analysis) in signals {
+        for frame in &analysis.frames {
+            write_row(
+                out,
+                delimiter,
+                [
+                    label.to_string(),
+                    frame.index.to_string(),
+                    format!("{:.6}", frame.time),
+                    (if frame.note.is_some() { "1" } else { "0" }).to_string(),
+                    format!("{:.3}", frame.freq),
+                    frame.note.map_or(String::new(), |n| n.to_string()),
+                    frame.note.map_or(String::new(), midi_note_to_name),
+                    frame
+                        .note
+                        .map_or(String::new(), |_| format!("{:.2}", frame.cents)),
+                    format!("{:.6}", frame.magnitude),
+                    format!("{:.4}", frame.confidence),
+                ]
+                .into_iter(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// One row per frame with the signal, frame, time and the 12 pitch classes
+pub fn write_chroma<W: Write>(
+    out: &mut W,
+    signals: &[(&str, &Chromagram)],
+    delimiter: Delimiter,
+) -> io::Result<()> {
+    let header = ["signal", "frame", "time"].map(String::from);
+    let classes = (0..12).map(|pc| pitch_class_name(pc).to_string());
+    write_row(out, delimiter, header.into_iter().chain(classes))?;
+    for (label, chromagram) in signals {
+        for (index, chroma) in chromagram.frames.iter().enumerate() {
+            let fields = [
+                label.to_string(),
+                index.to_string(),
+                format!("{:.6}", chromagram.time(index)),
+            ];
+            let values = chroma.iter().map(|v| format!("{:.4}", v));
+            write_row(out, delimiter, fields.into_iter().chain(values))?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_row<W: Write>(
+    out: &mut W,
+    delimiter: Delimiter,
+    fields: impl Iterator<Item = String>,
+) -> io::Result<()> {
+    let sep = delimiter.as_char();
+    let mut line = String::new();
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            line.push(sep);
+        }
+        line.push_str(&escape(&field, sep));
+    }
+    line.push('\n');
+    out.write_all(line.as_bytes())
+}
+
+// Quote fields that contain the separator, quotes or line breaks
+fn escape(field: &str, sep: char) -> String {
+    if field.contains([sep, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Frame;
+
+    fn frame(index: usize, freq: f32, note: Option<u8>) -> Frame {
+        Frame {
+            index,
+            time: index as f32 * 0.5,
+            freq,
+            magnitude: 0.25,
+            confidence: 0.5,
+            note,
+            cents: 3.0,
+            voices: Vec::new(),
+            onset_strength: 0.0,
+        }
+    }
+
+    #[test]
+    fn rejected_frames_are_unvoiced_rows() {
+        let analysis = Analysis {
+            sample_rate: 8000.0,
+            hop_size: 4000,
+            frames: vec![frame(0, 441.0, Some(69)), frame(1, 30.0, None)],
+            onsets: Vec::new(),
+            note_counts: Vec::new(),
+        };
+        let mut out = Vec::new();
+        write_pitch_track(&mut out, &[("a, b", &analysis)], Delimiter::Comma).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "signal,frame,time,voiced,freq,midi_note,note_name,cents,magnitude,confidence",
+                "\"a, b\",0,0.000000,1,441.000,69,A4,3.00,0.250000,0.5000",
+                "\"a, b\",1,0.500000,0,30.000,,,,0.250000,0.5000",
+            ]
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(escape("plain", ','), "plain");
+        assert_eq!(escape("a,b", ','), "\"a,b\"");
+        assert_eq!(escape("a,b", '\t'), "a,b");
+        assert_eq!(escape("a\tb", '\t'), "\"a\tb\"");
+        assert_eq!(escape("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("two\nlines", ','), "\"two\nlines\"");
+        assert_eq!(escape("cr\r", '\t'), "\"cr\r\"");
+    }
+}
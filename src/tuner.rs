@@ -0,0 +1,270 @@
+use crate::analysis::Frame;
+use crate::notes::freq_to_midi;
+
+// Rows of the block font
+pub const GLYPH_HEIGHT: usize = 5;
+// Cells of the cents meter, 2.5 cents each from -50 to +50
+pub const METER_CELLS: usize = 41;
+// Readings survive this long without a voiced frame, so a note that fades
+// or drops out for a frame does not blank the display
+const HOLD: f32 = 0.5;
+// Pitch changes beyond this many semitones restart the smoothing, so a new
+// note shows at once instead of sliding over from the last one
+const JUMP: f32 = 0.5;
+
+// Block font for note names, '#' marking filled cells
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', [" ### ", "#   #", "#####", "#   #", "#   #"]),
+    ('B', ["#### ", "#   #", "#### ", "#   #", "#### "]),
+    ('C', [" ####", "#    ", "#    ", "#    ", " ####"]),
+    ('D', ["#### ", "#   #", "#   #", "#   #", "#### "]),
+    ('E', ["#####", "#    ", "#### ", "#    ", "#####"]),
+    ('F', ["#####", "#    ", "#### ", "#    ", "#    "]),
+    ('G', [" ####", "#    ", "#  ##", "#   #", " ####"]),
+    ('#', [" # # ", "#####", " # # ", "#####", " # # "]),
+    ('b', ["#   ", "#   ", "### ", "#  #", "### "]),
+    ('0', [" ## ", "#  #", "#  #", "#  #", " ## "]),
+    ('1', [" #  ", "##  ", " #  ", " #  ", "### "]),
+    ('2', ["### ", "   #", " ## ", "#   ", "####"]),
+    ('3', ["### ", "   #", " ## ", "   #", "### "]),
+    ('4', ["#  #", "#  #", "####", "   #", "   #"]),
+    ('5', ["####", "#   ", "### ", "   #", "### "]),
+    ('6', [" ## ", "#   ", "### ", "#  #", " ## "]),
+    ('7', ["####", "   #", "  # ", " #  ", " #  "]),
+    ('8', [" ## ", "#  #", " ## ", "#  #", " ## "]),
+    ('9', [" ## ", "#  #", " ###", "   #", " ## "]),
+    ('-', ["   ", "   ", "###", "   ", "   "]),
+    ('\'', ["#", "#", " ", " ", " "]),
+    (',', [" ", " ", " ", "#", "#"]),
+];
+
+// Smoothed pitch shown by the tuner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    pub note: u8,
+    pub freq: f32,
+    // Deviation from `note`, -50 to 50
+    pub cents: f32,
+}
+
+// Turns the frame by frame pitch into a steady reading. Pitch is averaged
+// on the semitone scale with an exponential moving average, restarted
+// whenever it jumps to another note.
+#[derive(Debug, Clone)]
+pub struct Tuner {
+    reference: f32,
+    // Weight of each new frame in the average
+    alpha: f32,
+    hold_frames: usize,
+    // Smoothed pitch as a fractional MIDI note
+    pitch: Option<f32>,
+    silent_frames: usize,
+}
+
+impl Tuner {
+    // `time_constant` is the smoothing in seconds (0 for none) and
+    // `hop_seconds` the time between frames
+    pub fn new(reference: f32, time_constant: f32, hop_seconds: f32) -> Self {
+        let alpha = if time_constant > 0.0 {
+            1.0 - (-hop_seconds / time_constant).exp()
+        } else {
+            1.0
+        };
+        Tuner {
+            reference,
+            alpha,
+            hold_frames: (HOLD / hop_seconds.max(f32::EPSILON)).round() as usize,
+            pitch: None,
+            silent_frames: 0,
+        }
+    }
+
+    // Take in the next frame; None once nothing has sounded for a while
+    pub fn update(&mut self, frame: &Frame) -> Option<Reading> {
+        if frame.note.is_some() && frame.freq > 0.0 {
+            let pitch = freq_to_midi(frame.freq, self.reference);
+            self.pitch = Some(match self.pitch {
+                Some(last) if (pitch - last).abs() <= JUMP => last + self.alpha * (pitch - last),
+                _ => pitch,
+            });
+            self.silent_frames = 0;
+        } else {
+            self.silent_frames += 1;
+            if self.silent_frames > self.hold_frames {
+                self.pitch = None;
+            }
+        }
+        self.reading()
+    }
+
+    pub fn reading(&self) -> Option<Reading> {
+        let pitch = self.pitch?;
+        let note = pitch.round().clamp(0.0, 127.0);
+        Some(Reading {
+            note: note as u8,
+            freq: self.reference * 2f32.powf((pitch - 69.0) / 12.0),
+            cents: (pitch - note) * 100.0,
+        })
+    }
+}
+
+// A note name drawn in the block font, GLYPH_HEIGHT rows. The leading
+// letter is drawn as a capital, ♯ and ♭ as # and b; characters without a
+// glyph become spaces.
+pub fn big_text(text: &str, fill: char) -> Vec<String> {
+    let mut rows = vec![String::new(); GLYPH_HEIGHT];
+    for (i, c) in text.chars().enumerate() {
+        let c = match c {
+            c if i == 0 => c.to_ascii_uppercase(),
+            '\u{266f}' => '#',
+            '\u{266d}' => 'b',
+            c => c,
+        };
+        let glyph = GLYPHS.iter().find(|(g, _)| *g == c).map(|(_, rows)| rows);
+        for (row, out) in rows.iter_mut().enumerate() {
+            if i > 0 {
+                out.push(' ');
+            }
+            match glyph {
+                Some(glyph) => out.extend(
+                    glyph[row]
+                        .chars()
+                        .map(|p| if p == '#' { fill } else { ' ' }),
+                ),
+                None => out.push_str("   "),
+            }
+        }
+    }
+    rows
+}
+
+// Cents meter as three lines: labels, the scale and a pointer under the
+// given deviation. The scale starts two columns in to leave room for -50.
+pub fn meter(cents: f32) -> [String; 3] {
+    let mut labels = vec![' '; METER_CELLS + 4];
+    for (i, label) in ["-50", "-25", "0", "+25", "+50"].iter().enumerate() {
+        let start = 2 + i * 10 - label.len() / 2;
+        for (j, c) in label.chars().enumerate() {
+            labels[start + j] = c;
+        }
+    }
+    let scale: String = (0..METER_CELLS)
+        .map(|i| match i % 10 {
+            0 => '|',
+            5 => '+',
+            _ => '-',
+        })
+        .collect();
+    let position = ((cents.clamp(-50.0, 50.0) + 50.0) / 2.5).round() as usize;
+    [
+        labels
+            .into_iter()
+            .collect::<String>()
+            .trim_end()
+            .to_string(),
+        format!("  {}", scale),
+        format!("  {}^", " ".repeat(position)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(freq: Option<f32>) -> Frame {
+        Frame {
+            index: 0,
+            time: 0.0,
+            freq: freq.unwrap_or(0.0),
+            magnitude: 1.0,
+            confidence: 1.0,
+            note: freq.map(|f| freq_to_midi(f, 440.0).round() as u8),
+            cents: 0.0,
+            voices: Vec::new(),
+            onset_strength: 0.0,
+        }
+    }
+
+    #[test]
+    fn smoothing_jumps_and_hold() {
+        // Frames 50 ms apart, a 100 ms time constant and a 10 frame hold
+        let mut tuner = Tuner::new(440.0, 0.1, 0.05);
+        let alpha = 1.0 - (-0.5f32).exp();
+        assert_eq!(tuner.update(&frame(None)), None);
+
+        let reading = tuner.update(&frame(Some(440.0))).unwrap();
+        assert_eq!((reading.note, reading.freq), (69, 440.0));
+        // 20 cents sharp moves the reading only part of the way
+        let reading = tuner
+            .update(&frame(Some(440.0 * 2f32.powf(0.2 / 12.0))))
+            .unwrap();
+        assert_eq!(reading.note, 69);
+        assert!(
+            (reading.cents - 20.0 * alpha).abs() < 0.01,
+            "{}",
+            reading.cents
+        );
+
+        // A new note shows at once
+        let reading = tuner.update(&frame(Some(523.2511))).unwrap();
+        assert_eq!(reading.note, 72);
+        assert!(reading.cents.abs() < 0.01, "{}", reading.cents);
+
+        // and is held through silence until the hold runs out
+        for _ in 0..10 {
+            assert_eq!(tuner.update(&frame(None)).map(|r| r.note), Some(72));
+        }
+        assert_eq!(tuner.update(&frame(None)), None);
+        assert_eq!(tuner.reading(), None);
+    }
+
+    #[test]
+    fn no_smoothing() {
+        let mut tuner = Tuner::new(440.0, 0.0, 0.05);
+        tuner.update(&frame(Some(440.0)));
+        let reading = tuner
+            .update(&frame(Some(440.0 * 2f32.powf(0.2 / 12.0))))
+            .unwrap();
+        assert!((reading.cents - 20.0).abs() < 0.01, "{}", reading.cents);
+    }
+
+    #[test]
+    fn meter_pointer() {
+        let [labels, scale, _] = meter(0.0);
+        assert_eq!(labels, " -50       -25        0        +25       +50");
+        assert_eq!(scale, "  |----+----|----+----|----+----|----+----|");
+        // The pointer sits under the scale cell nearest the deviation
+        for (cents, column) in [(0.0, 22), (-50.0, 2), (12.4, 27), (-3.0, 21), (80.0, 42)] {
+            let pointer = &meter(cents)[2];
+            assert_eq!(pointer.find('^'), Some(column), "{}", cents);
+            assert_eq!(pointer.len(), column + 1);
+        }
+    }
+
+    #[test]
+    fn big_note_names() {
+        assert_eq!(
+            big_text("C#4", '#'),
+            [
+                " ####  # #  #  #",
+                "#     ##### #  #",
+                "#      # #  ####",
+                "#     #####    #",
+                " ####  # #     #",
+            ]
+        );
+        // Accidentals drawn as # and b, the first letter as a capital
+        assert_eq!(big_text("f\u{266f}", '#'), big_text("F#", '#'));
+        assert_eq!(big_text("B\u{266d}", '#'), big_text("bb", '#'));
+        assert_eq!(big_text("Bb", '@')[2], "@@@@  @@@ ");
+        // Helmholtz octave ticks and commas sit high and low
+        let ticks = big_text("c''", '#');
+        assert_eq!(ticks[0], " #### # #");
+        assert_eq!(ticks[4], " ####    ");
+        let commas = big_text("C,", '#');
+        assert_eq!(commas[0], " ####  ");
+        assert_eq!(commas[4], " #### #");
+        // Characters without a glyph leave a gap
+        assert_eq!(big_text("A?", '#')[0], " ###     ");
+    }
+}
@@ -0,0 +1,161 @@
+use crate::analysis::Analysis;
+use crate::notes::shift_reference;
+use std::f32::consts::TAU;
+
+// One-cent bins covering a semitone, -50 to +50 cents
+pub const BINS: usize = 100;
+// Half width of the triangular kernel that smooths the histogram, in bins
+const SMOOTHING: usize = 5;
+// Deviations this close to the histogram peak are averaged into the offset
+const WINDOW: f32 = 25.0;
+
+// Dominant tuning of a recording relative to the reference it was analyzed with
+#[derive(Debug, Clone)]
+pub struct TuningEstimate {
+    // Offset from the reference in cents, -50 to +50
+    pub offset: f32,
+    // How tightly the deviations cluster, 0 (spread over the whole semitone)
+    // to 1 (every frame at the same offset)
+    pub confidence: f32,
+    // Number of voices the estimate is based on
+    pub count: usize,
+    // Voices per one-cent bin, the first bin starting at -50 cents
+    pub histogram: Vec<usize>,
+}
+
+impl TuningEstimate {
+    // Reference pitch that puts the dominant tuning at 0 cents
+    pub fn reference(&self, a4: f32) -> f32 {
+        shift_reference(a4, self.offset)
+    }
+}
+
+// Wrap a deviation in cents into [-50, 50)
+fn wrap(cents: f32) -> f32 {
+    (cents + 50.0).rem_euclid(100.0) - 50.0
+}
+
+// Deviation in cents of every voice of every analysis, the fractional part
+// of its MIDI note number
+fn deviations<'a>(analyses: &'a [&'a Analysis]) -> impl Iterator<Item = f32> + 'a {
+    analyses
+        .iter()
+        .flat_map(|a| &a.frames)
+        .flat_map(|f| &f.voices)
+        .map(|v| wrap(v.cents))
+}
+
+// Global tuning from the peak of the histogram of cents deviations. The
+// semitone wraps around, so +49 and -49 cents are neighbours.
+pub fn estimate(analyses: &[&Analysis]) -> Option<TuningEstimate> {
+    let mut histogram = vec![0; BINS];
+    for cents in deviations(analyses) {
+        histogram[((cents + 50.0) as usize).min(BINS - 1)] += 1;
+    }
+    let count: usize = histogram.iter().sum();
+    if count == 0 {
+        return None;
+    }
+
+    let smoothed: Vec<usize> = (0..BINS)
+        .map(|bin| {
+            (0..=2 * SMOOTHING)
+                .map(|k| {
+                    let weight = SMOOTHING + 1 - k.abs_diff(SMOOTHING);
+                    histogram[(bin + BINS + k - SMOOTHING) % BINS] * weight
+                })
+                .sum()
+        })
+        .collect();
+    let peak = (0..BINS).max_by_key(|&bin| smoothed[bin])?;
+
+    // Average the deviations around the peak, measured from its centre so
+    // values on the far side of the wrap count as close
+    let centre = peak as f32 - 50.0 + 0.5;
+    let (mut sum, mut near) = (0.0, 0);
+    for delta in deviations(analyses).map(|cents| wrap(cents - centre)) {
+        if delta.abs() <= WINDOW {
+            sum += delta;
+            near += 1;
+        }
+    }
+    let offset = wrap(centre + sum / near.max(1) as f32);
+
+    // Length of the mean resultant with each deviation as an angle around
+    // the semitone circle
+    let (mut x, mut y) = (0.0, 0.0);
+    for cents in deviations(analyses) {
+        let angle = cents / 100.0 * TAU;
+        x += angle.cos();
+        y += angle.sin();
+    }
+    let confidence = (x * x + y * y).sqrt() / count as f32;
+
+    Some(TuningEstimate {
+        offset,
+        confidence,
+        count,
+        histogram,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{Frame, Voice};
+
+    fn analysis(cents: &[f32]) -> Analysis {
+        let frames = cents
+            .iter()
+            .enumerate()
+            .map(|(index, &cents)| Frame {
+                index,
+                time: index as f32,
+                freq: 440.0,
+                magnitude: 1.0,
+                confidence: 1.0,
+                note: Some(69),
+                cents,
+                voices: vec![Voice {
+                    note: 69,
+                    freq: 440.0,
+                    cents,
+                    magnitude: 1.0,
+                }],
+                onset_strength: 0.0,
+            })
+            .collect();
+        Analysis {
+            sample_rate: 1.0,
+            hop_size: 1,
+            frames,
+            onsets: Vec::new(),
+            note_counts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn no_voices() {
+        assert!(estimate(&[&analysis(&[])]).is_none());
+    }
+
+    #[test]
+    fn steady_offset() {
+        let tuning = estimate(&[&analysis(&[-14.0, -13.0, -14.0, -15.0])]).unwrap();
+        assert!((tuning.offset + 14.0).abs() < 0.01, "{}", tuning.offset);
+        assert!(tuning.confidence > 0.99);
+        assert_eq!(tuning.count, 4);
+        assert_eq!(tuning.histogram.iter().sum::<usize>(), 4);
+    }
+
+    #[test]
+    fn offset_wraps_around_the_semitone() {
+        // Deviations either side of +-50 cents are the same tuning, a
+        // quarter tone off, not two clusters averaging to 0
+        let tuning = estimate(&[&analysis(&[48.0, 49.0, -49.0, -48.0, 47.0, -47.0])]).unwrap();
+        assert!(tuning.offset.abs() > 49.0, "{}", tuning.offset);
+        assert!(tuning.confidence > 0.95);
+        let tuning = estimate(&[&analysis(&[46.0, 47.0, -49.0])]).unwrap();
+        assert!((tuning.offset - 48.0).abs() < 0.01, "{}", tuning.offset);
+    }
+}